@@ -4,11 +4,15 @@
     reason = "less restrictions on build-time tools"
 )]
 
-use anyhow::{Context as _, Result};
-use chaud_cli::{actual_args, link_args, run};
-use std::env;
-use std::ffi::OsStr;
-use std::process::Command;
+use anyhow::{Context as _, Result, ensure};
+use chaud_cli::cfg::Target;
+use chaud_cli::config::CargoConfig;
+use chaud_cli::{actual_args, env_rustflags, env_str, link_args, linker_flag, run, verbose};
+use nanoserde::DeJson;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::{env, fs, io};
 
 fn main() -> Result<()> {
     let args = actual_args()?;
@@ -19,16 +23,41 @@ fn main() -> Result<()> {
         _ => &args,
     };
 
+    if let Some(("clean", metadata_flags)) = args.split_first().map(|(h, t)| (h.as_str(), t)) {
+        return clean(metadata_flags);
+    }
+
     let mut args = args.splitn(2, |a| a == "--");
     let build_flags = args.next().unwrap_or(&[]);
     let run_flags = args.next().unwrap_or(&[]);
 
-    let mut rust_flags = env::var_os("RUSTFLAGS").unwrap_or_default();
-    if !rust_flags.is_empty() {
-        rust_flags.push(" ");
-    }
-    rust_flags.push("-Clink-dead-code ");
-    rust_flags.push(link_args()?.join(" "));
+    let chaud_flags: Vec<_> = ["-Clink-dead-code"]
+        .iter()
+        .chain(link_args()?)
+        .map(|f| (*f).to_owned())
+        .collect();
+
+    let mut target = match extract_target(build_flags).context("Failed to extract target")? {
+        Some(t) => Target::new(t),
+        None => Target::host().context("Failed to determine host")?,
+    };
+    let cwd = env::current_dir().context("Failed to get the current directory")?;
+    let config =
+        CargoConfig::load(&cwd, build_flags).context("Failed to read the Cargo configuration")?;
+
+    let rust_flags = RustFlags::new(&chaud_flags, &config, &mut target)
+        .context("Failed to determine rustflags")?;
+    let linker = match env::var_os("CHAUD_LINKER") {
+        // Set by the user.
+        Some(_) => None,
+        // Also passed if nothing is configured, so `chaud-linker` does not
+        // need to read the configuration again.
+        None => {
+            let linker =
+                configured_linker(&config, &mut target).context("Failed to determine linker")?;
+            Some(linker.unwrap_or_else(|| "cc".into()))
+        }
+    };
 
     let feature_flags =
         extract_feature_flags(build_flags).context("Failed to extract feature flags")?;
@@ -36,16 +65,114 @@ fn main() -> Result<()> {
     let mut cmd = cargo();
     cmd.arg("run")
         .args(build_flags)
-        .arg("-Fchaud/unsafe-hot-reload")
-        .arg("--")
+        .arg("-Fchaud/unsafe-hot-reload");
+    rust_flags.apply(&mut cmd)?;
+    if let Some(linker) = linker {
+        verbose!("Linking via {linker:?}");
+        cmd.env("CHAUD_LINKER", linker);
+    }
+    cmd.arg("--")
         .args(run_flags)
-        .env("RUSTFLAGS", rust_flags)
-        .env("RUSTC_BOOTSTRAP", "1")
         .env("CHAUD_FEATURE_FLAGS", feature_flags);
 
     run(cmd)
 }
 
+/// How Chaud's `rustc` flags are passed to cargo, without overriding the
+/// flags set in the environment or the Cargo configuration.
+enum RustFlags {
+    /// Appended to an environment variable that is already set, which takes
+    /// precedence over the configuration.
+    Env(&'static str, String),
+    /// A `--config` value, which is merged with the configuration.
+    Config(String),
+}
+
+impl RustFlags {
+    fn new(flags: &[String], config: &CargoConfig, target: &mut Target) -> Result<Self> {
+        if let Some(encoded) = env_str("CARGO_ENCODED_RUSTFLAGS")? {
+            let encoded = append(encoded, flags, "\x1f");
+            return Ok(Self::Env("CARGO_ENCODED_RUSTFLAGS", encoded));
+        }
+
+        if let Some(rust_flags) = env_str("RUSTFLAGS")? {
+            let rust_flags = append(rust_flags, flags, " ");
+            return Ok(Self::Env("RUSTFLAGS", rust_flags));
+        }
+
+        // `build.rustflags` are ignored if any `target.*.rustflags` apply, in
+        // which case `target.*.rustflags` are joined.
+        let key = match config.has_target_rustflags(target)? {
+            true => "target.'cfg(all())'.rustflags",
+            false => "build.rustflags",
+        };
+
+        let array: Vec<_> = flags.iter().map(|f| toml_str(f)).collect();
+        Ok(Self::Config(format!("{key}=[{}]", array.join(", "))))
+    }
+
+    fn apply(&self, cmd: &mut Command) -> Result<()> {
+        match self {
+            Self::Env(var, flags) => {
+                cmd.env(var, flags);
+            }
+            Self::Config(config) => {
+                let args = ["--config", config.as_str()];
+                // The worker passes the same arguments to cargo when
+                // rebuilding.
+                cmd.args(args)
+                    .env("__CHAUD_CARGO_ARGS", shlex::try_join(args)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn extract_target(args: &[String]) -> Result<Option<String>> {
+    use lexopt::prelude::*;
+
+    let mut parser = lexopt::Parser::from_args(args);
+
+    let mut target = None;
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Long("target") => target = Some(parser.value()?.parse::<String>()?),
+            Short(_) | Long(_) => {
+                parser.optional_value();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(target)
+}
+
+/// The linker `chaud-linker` should run instead of `cc`, as configured by the
+/// user. `-Clinker` from the environment's rustflags takes precedence over the
+/// Cargo configuration, like it does for Cargo.
+fn configured_linker(config: &CargoConfig, target: &mut Target) -> Result<Option<OsString>> {
+    if let Some(linker) = env_rustflags()?.and_then(|f| linker_flag(&f)) {
+        return Ok(Some(linker.into()));
+    }
+
+    Ok(config.linker(target)?.map(PathBuf::into_os_string))
+}
+
+fn append(mut existing: String, flags: &[String], sep: &str) -> String {
+    for flag in flags {
+        if !existing.is_empty() {
+            existing.push_str(sep);
+        }
+        existing.push_str(flag);
+    }
+    existing
+}
+
+/// Quote `s` as a TOML basic string.
+fn toml_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 fn extract_feature_flags(args: &[String]) -> Result<String> {
     use lexopt::prelude::*;
 
@@ -69,6 +196,124 @@ fn extract_feature_flags(args: &[String]) -> Result<String> {
     Ok(shlex::try_join(features.iter().map(|s| s.as_str()))?)
 }
 
+/// Remove everything Chaud produced in the target directory: The `chaud`
+/// directories containing hot-reloaded libraries, and `-Csave-temps`
+/// artifacts (of binaries and examples).
+fn clean(metadata_flags: &[String]) -> Result<()> {
+    #[derive(DeJson)]
+    struct Metadata {
+        target_directory: String,
+    }
+
+    let mut cmd = cargo();
+    cmd.args(["metadata", "--format-version=1", "--no-deps"])
+        .args(metadata_flags)
+        .stderr(Stdio::inherit());
+
+    verbose!("Executing: {cmd:?}");
+    let output = cmd.output().context("Failed to spawn `cargo metadata`")?;
+    ensure!(
+        output.status.success(),
+        "Failed to run ({}): {cmd:?}",
+        output.status
+    );
+
+    let output = String::from_utf8(output.stdout).context("Invalid UTF-8 from `cargo metadata`")?;
+    let metadata =
+        Metadata::deserialize_json(&output).context("Failed to parse `cargo metadata` output")?;
+    let target = Path::new(&metadata.target_directory);
+
+    let mut removed = 0_usize;
+
+    // Profile directories are either direct children of the target directory,
+    // or nested in a directory named after the target triple.
+    for dir in subdirs(target)? {
+        for dir in [dir.clone()].into_iter().chain(subdirs(&dir)?) {
+            if !is_profile_dir(&dir) {
+                continue;
+            }
+            removed += clean_profile(&dir).with_context(|| format!("Failed to clean {dir:?}"))?;
+        }
+    }
+
+    eprintln!("Removed {removed} files from {target:?}");
+
+    Ok(())
+}
+
+/// Whether `dir` looks like a profile directory produced by Cargo, as opposed
+/// to e.g. `target/doc`.
+fn is_profile_dir(dir: &Path) -> bool {
+    dir.join("deps").is_dir() && (dir.join(".fingerprint").is_dir() || dir.join("build").is_dir())
+}
+
+fn clean_profile(dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+
+    let chaud = dir.join("chaud");
+    if chaud.is_dir() {
+        for entry in fs::read_dir(&chaud)? {
+            let entry = entry?;
+            if is_hot_lib(&entry.file_name().to_string_lossy()) {
+                verbose!("Removing {:?}", entry.path());
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+
+        // Only if nothing else is left.
+        if fs::remove_dir(&chaud).is_ok() {
+            verbose!("Removed {chaud:?}");
+        }
+    }
+
+    // The objects of examples are kept next to the example binaries.
+    for objs in [dir.join("deps"), dir.join("examples")] {
+        if !objs.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&objs)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().contains(".rcgu.") {
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Whether `name` is a library named like `<bin>.<pid>.<epoch>.hot` (see
+/// `chaud_hot::workspace::clean`).
+fn is_hot_lib(name: &str) -> bool {
+    let Some(stem) = name.strip_suffix(".hot") else {
+        return false;
+    };
+
+    let mut parts = stem.rsplitn(3, '.');
+    let is_num =
+        |p: Option<&str>| p.is_some_and(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()));
+    is_num(parts.next()) && is_num(parts.next()) && parts.next().is_some_and(|b| !b.is_empty())
+}
+
+fn subdirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {dir:?}")),
+    };
+
+    let mut dirs = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
 fn cargo() -> Command {
     let cargo = env::var_os("CARGO");
     let cargo = cargo
@@ -77,3 +322,25 @@ fn cargo() -> Command {
 
     Command::new(cargo)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_lib() {
+        assert!(is_hot_lib("root.1234.1.hot"));
+        assert!(is_hot_lib("my.app.1234.12.hot"));
+
+        for name in [
+            "root.1234.1",
+            "root.x.1.hot",
+            "root.1234..hot",
+            ".1234.1.hot",
+            "1.hot",
+            "index.html",
+        ] {
+            assert!(!is_hot_lib(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Used via `-Clinker=chaud-linker`. Runs the actual linker with all static
+//! libraries included in full, which otherwise requires `-Zpre-link-args`.
+//!
+//! The actual linker is `CHAUD_LINKER` (set by `cargo chaud`), or else the
+//! linker configured for the target (other than `chaud-linker`) via `-Clinker`
+//! in the rustflags from the environment, `CARGO_TARGET_<triple>_LINKER` or the
+//! Cargo configuration, or `cc`. The configuration is found starting from the
+//! working directory of `rustc`, which is the workspace root, so `--config`
+//! arguments and configuration files only found from a different directory
+//! aren't seen.
+//!
+//! The name must not end with `-ld`, otherwise `rustc` would assume that this
+//! is `ld` and pass arguments without `-Wl,`.
+#![allow(
+    clippy::missing_errors_doc,
+    reason = "less restrictions on build-time tools"
+)]
+
+use anyhow::{Context as _, Result};
+use chaud_cli::cfg::Target;
+use chaud_cli::config::CargoConfig;
+use chaud_cli::{actual_args, env_rustflags, linker_flag, run, verbose, whole_archive_arg};
+use std::env;
+use std::ffi::OsString;
+use std::process::Command;
+
+fn main() -> Result<()> {
+    let args = actual_args()?;
+
+    // Reload builds only print the linker invocation. `-Clinker=true` (see
+    // `chaud_hot::cargo::builder`) is overridden by `-Clinker=chaud-linker`
+    // from the rustflags, so do the same here.
+    if env::var_os("__CHAUD_RELOAD").is_some() {
+        return Ok(());
+    }
+
+    let linker = match env::var_os("CHAUD_LINKER").filter(|l| !l.is_empty()) {
+        Some(l) => l,
+        None => configured_linker(&args)
+            .context("Failed to determine the configured linker")?
+            .unwrap_or_else(|| "cc".into()),
+    };
+
+    let mut cmd = Command::new(linker);
+    cmd.arg(whole_archive_arg()?).args(args);
+
+    run(cmd)
+}
+
+/// The linker configured for the target, which is determined from the path of
+/// the standard library (`<sysroot>/lib/rustlib/<triple>/lib/...`) in `args`.
+fn configured_linker(args: &[String]) -> Result<Option<OsString>> {
+    // `rustc` inherits the environment of Cargo. As for Cargo, the rustflags
+    // take precedence over the configuration.
+    if let Some(linker) = env_rustflags()?.and_then(|f| linker_flag(&f)) {
+        return Ok(Some(linker.into()));
+    }
+
+    let triple = args.iter().find_map(|a| {
+        let (_, rest) = a.split_once("/lib/rustlib/")?;
+        rest.split_once('/').map(|(t, _)| t.to_owned())
+    });
+    let mut target = match triple {
+        Some(t) => Target::new(t),
+        None => Target::host()?,
+    };
+    verbose!("Target: {}", target.triple());
+
+    let cwd = env::current_dir().context("Failed to get the current directory")?;
+    let config = CargoConfig::load(&cwd, &[])?;
+
+    Ok(config.linker(&mut target)?.map(Into::into))
+}
@@ -3,7 +3,7 @@
     reason = "less restrictions on build-time tools"
 )]
 use anyhow::{Context as _, Result, bail};
-use chaud_cli::{actual_args, link_args, run};
+use chaud_cli::{actual_args, link_args, run, verbose};
 use std::env;
 use std::process::Command;
 
@@ -13,16 +13,29 @@ fn main() -> Result<()> {
         bail!("Too few arguments: missing `rustc` executable");
     };
 
-    let mut cmd = Command::new(prog);
-    cmd.args(args);
+    let extracted = match is_hot(args) {
+        true => Some(extract(args).context("Failed to extract feature flags")?),
+        false => None,
+    };
+    let is_hot_binary = extracted.as_ref().is_some_and(|e| e.is_binary);
 
-    if is_hot(args) {
-        let extracted = extract(args).context("Failed to extract feature flags")?;
+    let mut cmd = match inner_wrapper()? {
+        Some(wrapper) if is_cacheable(is_hot_binary) => {
+            let mut cmd = Command::new(wrapper);
+            cmd.arg(prog);
+            cmd
+        }
+        Some(_) => {
+            verbose!("Bypassing `CHAUD_RUSTC_WRAPPER` for uncacheable build");
+            Command::new(prog)
+        }
+        None => Command::new(prog),
+    };
+    cmd.args(args);
 
+    if let Some(extracted) = extracted {
         if extracted.is_binary {
-            cmd.arg("-Clink-dead-code")
-                .args(link_args()?)
-                .env("RUSTC_BOOTSTRAP", "1");
+            cmd.arg("-Clink-dead-code").args(link_args()?);
         }
 
         if env::var_os("CHAUD_FEATURE_FLAGS").is_none() {
@@ -33,6 +46,27 @@ fn main() -> Result<()> {
     run(cmd)
 }
 
+/// Another `RUSTC_WRAPPER` (e.g. `sccache`) that `rustc` is run with.
+fn inner_wrapper() -> Result<Option<String>> {
+    match env::var("CHAUD_RUSTC_WRAPPER") {
+        Ok(w) if w.is_empty() => Ok(None),
+        Ok(w) => Ok(Some(w)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => bail!("Invalid UTF-8 in CHAUD_RUSTC_WRAPPER"),
+    }
+}
+
+/// Whether the output of `rustc` may be served from the cache of the inner
+/// wrapper.
+///
+/// Reload builds are not: Their macro expansion depends on `__CHAUD_RELOAD`,
+/// and the worker relies on the object files from `-Csave-temps` and the
+/// output of `--print=link-args`. Neither are binaries with hot reloading
+/// enabled, which are linked with additional flags.
+fn is_cacheable(is_hot_binary: bool) -> bool {
+    !is_hot_binary && env::var_os("__CHAUD_RELOAD").is_none()
+}
+
 fn is_hot(args: &[String]) -> bool {
     args.iter().any(|s| s.contains("__chaud_hot_marker"))
 }
@@ -0,0 +1,287 @@
+//! Evaluating `cfg(...)` expressions, as used in `target.'cfg(...)'` keys of
+//! the Cargo configuration.
+
+use crate::verbose;
+use anyhow::{Context as _, Result, bail, ensure};
+use std::env;
+use std::process::{Command, Stdio};
+
+/// A target triple, and its `cfg` options (queried when first needed).
+#[derive(Debug)]
+pub struct Target {
+    triple: String,
+    cfg: Option<TargetCfg>,
+}
+
+impl Target {
+    #[must_use]
+    pub fn new(triple: String) -> Self {
+        Self { triple, cfg: None }
+    }
+
+    /// The host target of `rustc`.
+    pub fn host() -> Result<Self> {
+        let mut cmd = rustc();
+        cmd.arg("-vV").stderr(Stdio::inherit());
+
+        verbose!("Executing: {cmd:?}");
+        let output = cmd.output().context("Failed to spawn `rustc`")?;
+        ensure!(
+            output.status.success(),
+            "Failed to run ({}): {cmd:?}",
+            output.status
+        );
+
+        let output = String::from_utf8(output.stdout).context("Invalid UTF-8 from `rustc`")?;
+        let triple = output
+            .lines()
+            .find_map(|l| l.strip_prefix("host: "))
+            .context("No host in `rustc -vV` output")?;
+
+        Ok(Self::new(triple.to_owned()))
+    }
+
+    #[must_use]
+    pub fn triple(&self) -> &str {
+        &self.triple
+    }
+
+    /// Whether a `target.<key>` table of the Cargo configuration applies, with
+    /// `key` being either a triple or a `cfg(...)` expression.
+    pub fn applies(&mut self, key: &str) -> Result<bool> {
+        if !key.starts_with("cfg(") {
+            return Ok(key == self.triple);
+        }
+
+        let cfg = match &mut self.cfg {
+            Some(c) => c,
+            None => self.cfg.insert(TargetCfg::query(&self.triple)?),
+        };
+        cfg.matches(key)
+    }
+}
+
+/// The `cfg` options of a target, as printed by `rustc --print cfg`.
+#[derive(Debug)]
+pub struct TargetCfg {
+    options: Vec<(String, Option<String>)>,
+}
+
+impl TargetCfg {
+    /// Query the options of `triple` from `rustc`.
+    pub fn query(triple: &str) -> Result<Self> {
+        let mut cmd = rustc();
+        cmd.args(["--print", "cfg", "--target", triple])
+            .stderr(Stdio::inherit());
+
+        verbose!("Executing: {cmd:?}");
+        let output = cmd.output().context("Failed to spawn `rustc`")?;
+        ensure!(
+            output.status.success(),
+            "Failed to run ({}): {cmd:?}",
+            output.status
+        );
+
+        let output = String::from_utf8(output.stdout).context("Invalid UTF-8 from `rustc`")?;
+        Ok(Self::parse(&output))
+    }
+
+    /// Parse lines like `unix` or `target_os="linux"`.
+    pub fn parse(output: &str) -> Self {
+        let options = output
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| match l.split_once('=') {
+                Some((k, v)) => (k.to_owned(), Some(v.trim_matches('"').to_owned())),
+                None => (l.to_owned(), None),
+            })
+            .collect();
+
+        Self { options }
+    }
+
+    /// Evaluate `expr`, which looks like `cfg(<predicate>)`.
+    pub fn matches(&self, expr: &str) -> Result<bool> {
+        let eval = || {
+            let mut p = Parser { rest: expr };
+            p.expect("cfg")?;
+            p.expect("(")?;
+            let res = self.predicate(&mut p)?;
+            p.expect(")")?;
+            ensure!(p.rest.trim().is_empty(), "Trailing input: {:?}", p.rest);
+            Ok(res)
+        };
+
+        eval().with_context(|| format!("Failed to evaluate `{expr}`"))
+    }
+
+    fn predicate(&self, p: &mut Parser<'_>) -> Result<bool> {
+        let name = p.ident()?;
+        match name {
+            "all" | "any" => {
+                let mut results = vec![];
+                p.expect("(")?;
+                while !p.eat(")") {
+                    results.push(self.predicate(p)?);
+                    if !p.eat(",") {
+                        p.expect(")")?;
+                        break;
+                    }
+                }
+                Ok(match name {
+                    "all" => results.iter().all(|r| *r),
+                    _ => results.iter().any(|r| *r),
+                })
+            }
+            "not" => {
+                p.expect("(")?;
+                let res = self.predicate(p)?;
+                p.expect(")")?;
+                Ok(!res)
+            }
+            _ => {
+                let value = match p.eat("=") {
+                    true => Some(p.string()?),
+                    false => None,
+                };
+                Ok(self
+                    .options
+                    .iter()
+                    .any(|(k, v)| k == name && v.as_deref() == value))
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn eat(&mut self, token: &str) -> bool {
+        match self.rest.trim_start().strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        ensure!(self.eat(token), "Expected `{token}` at {:?}", self.rest);
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<&'a str> {
+        let rest = self.rest.trim_start();
+        let end = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            bail!("Expected an identifier at {rest:?}");
+        }
+        let (ident, rest) = rest.split_at(end);
+        self.rest = rest;
+        Ok(ident)
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        self.expect("\"")?;
+        let Some((s, rest)) = self.rest.split_once('"') else {
+            bail!("Unterminated string at {:?}", self.rest);
+        };
+        self.rest = rest;
+        Ok(s)
+    }
+}
+
+/// The `rustc` to use, respecting `RUSTC`.
+#[must_use]
+pub fn rustc() -> Command {
+    Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: &str = r#"
+debug_assertions
+panic="unwind"
+target_arch="x86_64"
+target_family="unix"
+target_os="linux"
+unix
+"#;
+
+    #[test]
+    fn matches() -> Result<()> {
+        let cfg = TargetCfg::parse(LINUX);
+
+        assert!(cfg.matches("cfg(unix)")?);
+        assert!(!cfg.matches("cfg(windows)")?);
+        assert!(cfg.matches(r#"cfg(target_os = "linux")"#)?);
+        assert!(!cfg.matches(r#"cfg(target_os="macos")"#)?);
+        assert!(!cfg.matches(r#"cfg(target_os)"#)?);
+        assert!(cfg.matches(r#"cfg(all(unix, not(target_os = "macos")))"#)?);
+        assert!(cfg.matches(r#"cfg(any(windows, target_arch = "x86_64",))"#)?);
+        assert!(cfg.matches("cfg(all())")?);
+        assert!(!cfg.matches("cfg(any())")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nesting() -> Result<()> {
+        let cfg = TargetCfg::parse(LINUX);
+
+        assert!(cfg.matches("cfg(not(not(unix)))")?);
+        assert!(!cfg.matches("cfg(not(all(unix, debug_assertions)))")?);
+        assert!(cfg.matches(
+            r#"cfg(any(all(unix, target_arch = "arm"), all(unix, target_arch = "x86_64")))"#
+        )?);
+        assert!(!cfg.matches(r#"cfg(all(any(windows, unix), not(any(target_os = "linux"))))"#)?);
+        assert!(cfg.matches(r#"cfg(all(not(windows), any(not(unix), panic = "unwind")))"#)?);
+        assert!(cfg.matches("cfg(not(any()))")?);
+        assert!(!cfg.matches("cfg(not(all()))")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_predicates() -> Result<()> {
+        let cfg = TargetCfg::parse(LINUX);
+
+        // Like for `rustc`, unknown options are unset.
+        assert!(!cfg.matches("cfg(foo)")?);
+        assert!(!cfg.matches(r#"cfg(foo = "bar")"#)?);
+        assert!(cfg.matches("cfg(not(foo))")?);
+        assert!(cfg.matches("cfg(any(foo, unix))")?);
+        assert!(!cfg.matches("cfg(all(foo, unix))")?);
+        // Options are case-sensitive.
+        assert!(!cfg.matches("cfg(Unix)")?);
+        assert!(!cfg.matches(r#"cfg(target_os = "Linux")"#)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let cfg = TargetCfg::parse(LINUX);
+
+        for expr in [
+            "unix",
+            "cfg(unix",
+            "cfg(unix) x",
+            "cfg(not())",
+            "cfg(a = b)",
+            "cfg(not(unix, windows))",
+            "cfg(all(unix)",
+            "cfg(any(unix windows))",
+        ] {
+            assert!(cfg.matches(expr).is_err(), "{expr}");
+        }
+    }
+}
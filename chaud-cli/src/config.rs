@@ -0,0 +1,331 @@
+//! Reading the Cargo configuration, without `cargo config` (which is unstable).
+
+use crate::cfg::Target;
+use crate::{env_str, is_chaud_linker, verbose};
+use anyhow::{Context as _, Result};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+use toml::{Table, Value};
+
+/// The Cargo configuration from `--config` arguments and config files.
+#[derive(Debug, Default)]
+pub struct CargoConfig {
+    /// Ordered by precedence, highest first.
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    key: Vec<String>,
+    value: Value,
+    /// The directory relative paths in `value` are relative to.
+    base: PathBuf,
+}
+
+impl Entry {
+    /// The value as a path, resolved like Cargo resolves executable paths:
+    /// Values without a `/` are looked up in `PATH`, other relative paths are
+    /// relative to the directory containing the `.cargo` directory.
+    #[must_use]
+    pub fn program(&self) -> Option<PathBuf> {
+        let Value::String(s) = &self.value else {
+            return None;
+        };
+
+        let path = Path::new(s);
+        match s.contains('/') && path.is_relative() {
+            true => Some(self.base.join(path)),
+            false => Some(path.to_owned()),
+        }
+    }
+}
+
+impl CargoConfig {
+    /// Load the configuration that applies to `cargo` running in `cwd`, with
+    /// `args` (from which `--config` arguments are extracted).
+    pub fn load(cwd: &Path, args: &[String]) -> Result<Self> {
+        let mut this = Self::default();
+
+        for value in config_args(args)? {
+            let path = cwd.join(&value);
+            match path.is_file() {
+                true => this.load_file(&path)?,
+                false => this.entries.extend(
+                    parse(&value, cwd).with_context(|| format!("Invalid `--config {value}`"))?,
+                ),
+            }
+        }
+
+        let mut files = vec![];
+        for dir in cwd.ancestors() {
+            files.push(dir.join(".cargo"));
+        }
+        if let Some(home) = cargo_home() {
+            if !files.contains(&home) {
+                files.push(home);
+            }
+        }
+
+        for dir in files {
+            for name in ["config.toml", "config"] {
+                let path = dir.join(name);
+                if path.is_file() {
+                    this.load_file(&path)?;
+                    // Cargo only reads `config` if there is no `config.toml`.
+                    break;
+                }
+            }
+        }
+
+        Ok(this)
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<()> {
+        verbose!("Reading {path:?}");
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+        };
+
+        // `<base>/.cargo/config.toml`
+        let base = path
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."));
+
+        let entries = parse(&content, base).with_context(|| format!("Failed to parse {path:?}"))?;
+        self.entries.extend(entries);
+        Ok(())
+    }
+
+    /// The entries for `target.<key>.<name>` that apply to `target`, in
+    /// order of precedence. `target.<triple>` takes precedence over
+    /// `target.<cfg>`.
+    fn target_entries(&self, target: &mut Target, name: &str) -> Result<Vec<&Entry>> {
+        let mut triple = vec![];
+        let mut cfg = vec![];
+
+        for entry in &self.entries {
+            let [t, key, n] = entry.key.as_slice() else {
+                continue;
+            };
+            if t != "target" || n != name {
+                continue;
+            }
+
+            match key.starts_with("cfg(") {
+                true if target.applies(key)? => cfg.push(entry),
+                true => {
+                    verbose!("Ignoring `target.{key}.{name}`");
+                }
+                false if key == target.triple() => triple.push(entry),
+                false => {}
+            }
+        }
+
+        triple.extend(cfg);
+        Ok(triple)
+    }
+
+    /// The linker configured for `target`, via `CARGO_TARGET_<triple>_LINKER`
+    /// or `target.<triple>.linker` (or `target.<cfg>.linker`).
+    ///
+    /// `chaud-linker` itself is skipped, since it is configured in place of
+    /// the actual linker.
+    pub fn linker(&self, target: &mut Target) -> Result<Option<PathBuf>> {
+        if let Some(linker) = target_env(target, "linker")? {
+            if !is_chaud_linker(&linker) {
+                return Ok(Some(linker.into()));
+            }
+        }
+
+        let entries = self.target_entries(target, "linker")?;
+        Ok(entries
+            .into_iter()
+            .filter_map(Entry::program)
+            .find(|p| !is_chaud_linker(p)))
+    }
+
+    /// Whether `target.<triple>.rustflags` (or `target.<cfg>.rustflags`)
+    /// apply to `target`, in which case Cargo ignores `build.rustflags`.
+    pub fn has_target_rustflags(&self, target: &mut Target) -> Result<bool> {
+        if target_env(target, "rustflags")?.is_some() {
+            return Ok(true);
+        }
+
+        Ok(!self.target_entries(target, "rustflags")?.is_empty())
+    }
+}
+
+/// The value of `CARGO_TARGET_<triple>_<name>`.
+pub fn target_env(target: &Target, name: &str) -> Result<Option<String>> {
+    let triple = target.triple().to_uppercase().replace(['-', '.'], "_");
+    env_str(&format!("CARGO_TARGET_{triple}_{}", name.to_uppercase()))
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("CARGO_HOME") {
+        return Some(home.into());
+    }
+
+    #[allow(deprecated, reason = "only deprecated before Rust 1.86")]
+    env::home_dir().map(|h| h.join(".cargo"))
+}
+
+fn config_args(args: &[String]) -> Result<Vec<String>> {
+    use lexopt::prelude::*;
+
+    let mut parser = lexopt::Parser::from_args(args);
+
+    let mut values = vec![];
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Long("config") => values.push(parser.value()?.parse::<String>()?),
+            Short(_) | Long(_) => {
+                parser.optional_value();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(values)
+}
+
+/// Parse `toml` into entries with dotted keys, one for each value that is not
+/// a table.
+fn parse(toml: &str, base: &Path) -> Result<Vec<Entry>> {
+    fn flatten(table: Table, key: &[String], base: &Path, entries: &mut Vec<Entry>) {
+        for (name, value) in table {
+            let mut key = key.to_vec();
+            key.push(name);
+            match value {
+                Value::Table(table) => flatten(table, &key, base, entries),
+                value => entries.push(Entry { key, value, base: base.to_owned() }),
+            }
+        }
+    }
+
+    let table = toml.parse::<Table>()?;
+    let mut entries = vec![];
+    flatten(table, &[], base, &mut entries);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn entries(toml: &str) -> Result<Vec<(String, Value)>> {
+        let entries = parse(toml, Path::new("/base"))?;
+        Ok(entries
+            .into_iter()
+            .map(|e| (e.key.join("|"), e.value))
+            .collect())
+    }
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    #[test]
+    fn tables() -> Result<()> {
+        let toml = r#"
+[build]
+rustflags = ["--cfg=foo"]
+jobs = 4
+
+[target.x86_64-unknown-linux-gnu]
+linker = "clang"
+
+[target.'cfg(all(unix, not(target_os = "macos")))']
+linker = '/usr/bin/cc'
+
+[env]
+FOO = { value = "bar", force = true }
+"#;
+
+        // Sorted by key.
+        assert_eq!(
+            entries(toml)?,
+            [
+                ("build|jobs".to_owned(), Value::Integer(4)),
+                (
+                    "build|rustflags".to_owned(),
+                    Value::Array(vec![s("--cfg=foo")])
+                ),
+                ("env|FOO|force".to_owned(), Value::Boolean(true)),
+                ("env|FOO|value".to_owned(), s("bar")),
+                (
+                    "target|cfg(all(unix, not(target_os = \"macos\")))|linker".to_owned(),
+                    s("/usr/bin/cc")
+                ),
+                (
+                    "target|x86_64-unknown-linux-gnu|linker".to_owned(),
+                    s("clang")
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dotted_keys() -> Result<()> {
+        // As passed via `--config`.
+        assert_eq!(
+            entries("target.'cfg(unix)'.linker = \"cc\"")?,
+            [("target|cfg(unix)|linker".to_owned(), s("cc"))]
+        );
+        assert_eq!(
+            entries("target = { aarch64-apple-darwin = { rustflags = [] } }")?,
+            [(
+                "target|aarch64-apple-darwin|rustflags".to_owned(),
+                Value::Array(vec![])
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        for toml in ["[build", "a = \"x", "a = 1 b = 2", "= 1", "a = 1\na = 2"] {
+            assert!(parse(toml, Path::new("/")).is_err(), "{toml}");
+        }
+    }
+
+    #[test]
+    fn program() {
+        let entry = |v: &str| Entry {
+            key: vec![],
+            value: s(v),
+            base: PathBuf::from("/ws"),
+        };
+
+        assert_eq!(entry("clang").program(), Some(PathBuf::from("clang")));
+        assert_eq!(
+            entry("tools/ld").program(),
+            Some(PathBuf::from("/ws/tools/ld"))
+        );
+        assert_eq!(
+            entry("/usr/bin/cc").program(),
+            Some(PathBuf::from("/usr/bin/cc"))
+        );
+    }
+
+    #[test]
+    fn skips_chaud_linker() -> Result<()> {
+        let config = CargoConfig {
+            entries: parse(
+                "target.'cfg(unix)'.linker = 'clang'\ntarget.x86_64-unknown-linux-gnu.linker = 'chaud-linker'",
+                Path::new("/ws"),
+            )?,
+        };
+
+        let mut target = Target::new("x86_64-unknown-linux-gnu".to_owned());
+        assert_eq!(config.linker(&mut target)?, Some(PathBuf::from("clang")));
+        Ok(())
+    }
+}
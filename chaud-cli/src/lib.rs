@@ -9,15 +9,20 @@ This crate implements shared functionality for the Chaud CLI Tools.
 */
 #![allow(
     clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
     reason = "less restrictions on build-time tools"
 )]
 
 use anyhow::{Result, bail, ensure};
 use core::fmt;
 use std::env;
+use std::path::Path;
 use std::process::Command;
 use std::sync::LazyLock;
 
+pub mod cfg;
+pub mod config;
+
 pub fn actual_args() -> Result<Vec<String>> {
     let mut args = vec![];
     for arg in env::args_os().skip(1) {
@@ -33,10 +38,10 @@ pub fn actual_args() -> Result<Vec<String>> {
 pub fn link_args() -> Result<&'static [&'static str]> {
     // See https://docs.rs/chaud#manual-setup.
     if cfg!(target_os = "macos") {
-        Ok(&["-Zpre-link-args=-Wl,-all_load"])
+        Ok(&["-Clinker=chaud-linker"])
     } else if cfg!(unix) {
         Ok(&[
-            "-Zpre-link-args=-Wl,--whole-archive",
+            "-Clinker=chaud-linker",
             "-Clink-args=-Wl,--allow-multiple-definition",
             "-Clink-args=-Wl,--export-dynamic",
         ])
@@ -45,6 +50,64 @@ pub fn link_args() -> Result<&'static [&'static str]> {
     }
 }
 
+/// The argument `chaud-linker` passes to the actual linker before all others,
+/// to include all symbols from static libraries.
+pub fn whole_archive_arg() -> Result<&'static str> {
+    if cfg!(target_os = "macos") {
+        Ok("-Wl,-all_load")
+    } else if cfg!(unix) {
+        Ok("-Wl,--whole-archive")
+    } else {
+        bail!("Hot-reloading not supported on the current platform");
+    }
+}
+
+/// The rustflags from `CARGO_ENCODED_RUSTFLAGS` or `RUSTFLAGS`, which take
+/// precedence over the Cargo configuration.
+pub fn env_rustflags() -> Result<Option<Vec<String>>> {
+    if let Some(encoded) = env_str("CARGO_ENCODED_RUSTFLAGS")? {
+        return Ok(Some(encoded.split('\x1f').map(str::to_owned).collect()));
+    }
+
+    let flags = env_str("RUSTFLAGS")?;
+    Ok(flags.map(|f| f.split_whitespace().map(str::to_owned).collect()))
+}
+
+pub fn env_str(var: &str) -> Result<Option<String>> {
+    match env::var(var) {
+        Ok(v) => Ok(Some(v)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => bail!("Invalid UTF-8 in {var}"),
+    }
+}
+
+/// Whether `linker` is `chaud-linker`, e.g. `-Clinker=chaud-linker`.
+pub fn is_chaud_linker(linker: impl AsRef<Path>) -> bool {
+    linker
+        .as_ref()
+        .file_stem()
+        .is_some_and(|s| s == "chaud-linker")
+}
+
+/// The value of the last `-Clinker` in `flags` other than `chaud-linker`, if
+/// any.
+#[must_use]
+pub fn linker_flag(flags: &[String]) -> Option<String> {
+    let mut linker = None;
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let value = match flag.strip_prefix("-C") {
+            Some("") => flags.next().map(String::as_str),
+            value => value,
+        };
+        let value = value.and_then(|v| v.strip_prefix("linker="));
+        if let Some(l) = value.filter(|l| !is_chaud_linker(l)) {
+            linker = Some(l.to_owned());
+        }
+    }
+    linker
+}
+
 pub fn run(mut cmd: Command) -> Result<()> {
     verbose!("Executing: {cmd:?}");
 
@@ -68,3 +131,25 @@ pub fn verbose(x: impl fmt::Display) {
 macro_rules! verbose {
     ($($t:tt)*) => { $crate::verbose(format_args!($($t)*)); }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn linker() {
+        let flags = |f: &[&str]| f.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>();
+
+        assert_eq!(linker_flag(&flags(&["--cfg=foo"])), None);
+        assert_eq!(linker_flag(&flags(&["-Clinker=a"])), Some("a".to_owned()));
+        assert_eq!(
+            linker_flag(&flags(&["-Clinker=a", "-C", "linker=b", "-Copt-level=1"])),
+            Some("b".to_owned())
+        );
+        assert_eq!(
+            linker_flag(&flags(&["-Clinker=a", "-Clinker=/bin/chaud-linker"])),
+            Some("a".to_owned())
+        );
+    }
+}
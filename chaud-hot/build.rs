@@ -6,9 +6,14 @@
 use std::env;
 
 fn main() {
-    let mut out_dir = env::var("OUT_DIR").expect("OUT_DIR to be valid UTF8");
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR to be valid UTF8");
+    let target = env::var("TARGET").expect("TARGET to be valid UTF8");
+
     // `chaud-rustc` uses this marker to detect when hot reloading is enabled.
-    out_dir.push_str("/__chaud_hot_marker");
+    println!("cargo::rustc-link-search=crate={out_dir}/__chaud_hot_marker");
 
-    println!("cargo::rustc-link-search=crate={out_dir}");
+    // `BuildEnv` determines the target dir, profile and target triple from
+    // these (see `workspace::graph::layout`).
+    println!("cargo::rustc-env=__CHAUD_OUT_DIR={out_dir}");
+    println!("cargo::rustc-env=__CHAUD_TARGET={target}");
 }
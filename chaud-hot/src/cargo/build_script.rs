@@ -0,0 +1,123 @@
+//! Reading the output of build scripts that Cargo has run.
+#![expect(clippy::question_mark, reason = "generated by `DeJson` for `Option`s")]
+
+use super::metadata::PackageId;
+use crate::util::normalize;
+use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use nanoserde::DeJson;
+use std::fs;
+
+/// The `rerun-if-*` directives printed by a build script.
+#[derive(Debug, Default)]
+pub struct RerunIf {
+    /// Files or directories, made absolute.
+    pub changed: Vec<Utf8PathBuf>,
+    pub env_changed: Vec<String>,
+}
+
+/// A message printed by Cargo, of which only `build-script-executed` ones have
+/// the optional fields.
+#[derive(DeJson)]
+struct Message {
+    reason: String,
+    package_id: Option<PackageId>,
+    out_dir: Option<String>,
+}
+
+/// A build script run reported by Cargo (`--message-format=json`), including
+/// runs that were fresh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Executed {
+    pub package_id: PackageId,
+    /// `target/<profile>/build/<pkg>-<hash>/out`
+    pub out_dir: Utf8PathBuf,
+}
+
+impl Executed {
+    /// Parse a line of Cargo's JSON output. Returns `None` for any other
+    /// message, or a line that isn't JSON at all.
+    pub fn parse(line: &str) -> Option<Self> {
+        let msg = Message::deserialize_json(line).ok()?;
+        if msg.reason != "build-script-executed" {
+            return None;
+        }
+
+        Some(Self {
+            package_id: msg.package_id?,
+            out_dir: msg.out_dir?.into(),
+        })
+    }
+
+    /// Read the directives printed by this run of the build script of the
+    /// package located in `pkg_dir`.
+    pub fn rerun_if(&self, pkg_dir: &Utf8Path) -> Result<RerunIf> {
+        // The output is stored next to the `out` directory.
+        let output = self.out_dir.with_file_name("output");
+        let buf =
+            fs::read_to_string(&output).with_context(|| format!("Failed to read {output:?}"))?;
+
+        let mut rerun = RerunIf::default();
+        for line in buf.lines() {
+            // Both the old `cargo:` and the new `cargo::` syntax.
+            let Some(line) = line.strip_prefix("cargo:") else {
+                continue;
+            };
+            let line = line.strip_prefix(':').unwrap_or(line);
+
+            if let Some(path) = line.strip_prefix("rerun-if-changed=") {
+                rerun.changed.push(normalize(&pkg_dir.join(path)));
+            } else if let Some(var) = line.strip_prefix("rerun-if-env-changed=") {
+                rerun.env_changed.push(var.to_owned());
+            }
+        }
+
+        log::trace!("Build script output {output:?}: {rerun:?}");
+        Ok(rerun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp::TempDir;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn executed() -> Result<()> {
+        let dir = TempDir::new("build-script")?;
+        let out_dir = dir.join("build/foo-0123abcd/out");
+        fs::create_dir_all(&out_dir)?;
+        fs::write(
+            out_dir.with_file_name("output"),
+            "cargo:rustc-cfg=foo\ncargo:rerun-if-changed=../shaders\ncargo::rerun-if-changed=build.rs\ncargo:rerun-if-env-changed=FOO\n",
+        )?;
+
+        // Abbreviated, as printed by `cargo build --message-format=json`.
+        let line = format!(
+            r#"{{"reason":"build-script-executed","package_id":"path+file:///ws/foo#0.1.0","linked_libs":[],"linked_paths":[],"cfgs":["foo"],"env":[],"out_dir":"{}"}}"#,
+            out_dir.as_str()
+        );
+        let executed = Executed::parse(&line).context("Not parsed")?;
+        assert_eq!(executed.out_dir, out_dir);
+        assert_eq!(
+            executed.package_id,
+            PackageId::deserialize_json(r#""path+file:///ws/foo#0.1.0""#)?
+        );
+
+        let rerun = executed.rerun_if(Utf8Path::new("/ws/foo"))?;
+        assert_eq!(rerun.changed, ["/ws/shaders", "/ws/foo/build.rs"]);
+        assert_eq!(rerun.env_changed, ["FOO"]);
+        Ok(())
+    }
+
+    #[test]
+    fn other_output() {
+        assert_eq!(
+            Executed::parse(r#"{"reason":"build-finished","success":true}"#),
+            None
+        );
+        assert_eq!(Executed::parse(r#""cc" "a.o""#), None);
+        assert_eq!(Executed::parse(""), None);
+    }
+}
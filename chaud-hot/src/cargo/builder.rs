@@ -1,10 +1,16 @@
 use super::StdioMode;
+use super::build_script::Executed;
+use super::dep_info;
+use super::hash::{ContentHash, HashCache};
+use super::linker::{Invocation, Linker};
+use crate::ReloadLinker;
 use crate::util::CommandExt as _;
 use crate::util::assert::err_unreachable;
+use crate::workspace::clean;
 use crate::workspace::graph::BuildEnv;
-use anyhow::{Context as _, Result, bail, ensure};
+use crate::{dylib, func};
+use anyhow::{Context as _, Result, bail};
 use camino::{Utf8Path, Utf8PathBuf};
-use core::iter::Peekable;
 use hashbrown::HashMap;
 use memchr::memmem;
 use nanoserde::DeJson;
@@ -15,69 +21,191 @@ use std::{fs, io};
 pub struct Builder {
     cmd: Command,
     linker: Linker,
-    initial: HashMap<Utf8PathBuf, SystemTime>,
-    latest: Vec<Utf8PathBuf>,
-}
-
-struct Linker {
-    env_clear: Box<[String]>,
-    env_set: Box<[(String, String)]>,
-    bin: String,
-    arg_pre: Box<[String]>,
-    arg_post: Box<[String]>,
+    hashes: HashCache,
+    /// The hashes of the rlibs linked into the binary.
+    initial: HashMap<Utf8PathBuf, ContentHash>,
+    /// The hashes of the rlibs and objects as of the newest library in
+    /// `deltas`.
+    linked: HashMap<Utf8PathBuf, ContentHash>,
+    /// The hashes of the rlibs and objects as of the newest loaded library.
+    loaded: HashMap<Utf8PathBuf, ContentHash>,
+    /// The libraries linked incrementally so far, oldest first.
+    deltas: Vec<Utf8PathBuf>,
+    /// The number of rollbacks as of the latest build.
+    rollbacks: u32,
+    /// The library linked incrementally by `link_latest`, until it is loaded.
+    pending: Option<Utf8PathBuf>,
+    /// The rlibs and objects of the latest (non-fresh) build, with their hashes.
+    latest: Vec<(Utf8PathBuf, ContentHash)>,
+    dep_infos: Vec<Utf8PathBuf>,
+    /// The build scripts run (or fresh) as of the latest build.
+    build_scripts: Vec<Executed>,
 }
 
 impl Builder {
-    pub fn init(env: &BuildEnv) -> Result<Self> {
-        init_inner(env).context("Failed to init Builder")
+    pub fn init(env: &BuildEnv, reload_linker: ReloadLinker) -> Result<Self> {
+        init_inner(env, reload_linker).context("Failed to init Builder")
+    }
+
+    /// Whether the latest build differs from the newest loaded library (or the
+    /// binary, if none was loaded yet). If not, there is nothing to reload.
+    ///
+    /// After a rollback, the loaded libraries no longer reflect the current
+    /// functions, so any build counts as changed.
+    pub fn has_changes(&self) -> bool {
+        self.latest
+            .iter()
+            .any(|(p, h)| self.loaded.get(p) != Some(h))
+    }
+
+    /// Link the latest build into the library `dst`.
+    ///
+    /// The library only contains what changed since the previous library, and
+    /// depends on the previous libraries for everything else. While unloading
+    /// is enabled, it instead contains everything that changed since the
+    /// binary was built, so that it does not depend on libraries that may be
+    /// unloaded.
+    ///
+    /// After a rollback, the previous libraries no longer reflect the current
+    /// functions. The library then contains everything that changed since the
+    /// binary was built as well, so that the entire latest build is applied,
+    /// and later libraries only depend on it.
+    pub fn link_latest(&mut self, dst: &Utf8Path) -> Result<()> {
+        let delta = !dylib::is_unloading();
+        let (base, deps) = match delta {
+            true => (&self.linked, self.deltas.as_slice()),
+            false => (&self.initial, [].as_slice()),
+        };
+
+        let files: Vec<_> = self
+            .latest
+            .iter()
+            .filter(|(p, h)| base.get(p) != Some(h))
+            .map(|(p, _)| p.clone())
+            .collect();
+        log::debug!(
+            "Linking {} of {} files, depending on {} previous libraries",
+            files.len(),
+            self.latest.len(),
+            deps.len()
+        );
+
+        // Newer libraries must take precedence, since they are searched in
+        // order (see `dylib::load`).
+        self.linker.link(dst, &files, deps.iter().rev())?;
+
+        self.pending = delta.then(|| dst.to_owned());
+        Ok(())
+    }
+
+    /// Record that the library linked by `link_latest` was loaded, so that
+    /// later libraries can depend on it.
+    pub fn did_load(&mut self) {
+        self.loaded.extend(self.latest.iter().cloned());
+
+        if let Some(dst) = self.pending.take() {
+            self.linked.extend(self.latest.iter().cloned());
+            self.deltas.push(dst);
+        }
     }
 
-    pub fn link_latest(&self, dst: &Utf8Path) -> Result<()> {
-        link(dst, &self.linker, &self.latest)
+    /// The dep-info files of all crates linked by the latest (non-fresh) build.
+    pub fn dep_infos(&self) -> &[Utf8PathBuf] {
+        &self.dep_infos
+    }
+
+    /// The build scripts of the latest build, whether they were run or fresh.
+    pub fn build_scripts(&self) -> &[Executed] {
+        &self.build_scripts
     }
 
     pub fn build(&mut self) -> Result<()> {
-        let parts = extract_link_args(&mut self.cmd).context("Build failed")?;
+        self.check_rollbacks();
+
+        let output = run_build(&mut self.cmd).context("Build failed")?;
+        // Reported for every build, unlike the linker args.
+        self.build_scripts = output.build_scripts;
 
-        // We need to check this, because the linker args won't be re-printed for a
-        // fully fresh build, and we need to avoid clearing `latest_libs` in that
-        // case.
-        if parts.is_empty() {
+        let Some(inv) = output.invocation else {
+            // We need to check this, because the linker args won't be re-printed
+            // for a fully fresh build, and we need to avoid clearing
+            // `latest_libs` in that case.
             log::trace!("Empty output, rlibs seem fresh");
             return Ok(());
-        }
-
-        self.latest.clear();
-        extract_libs(parts, &self.initial, |p, _| {
+        };
+
+        // The objects of the root crate are kept around with `-Csave-temps`,
+        // which would otherwise accumulate over time.
+        let objs: Vec<_> = inv
+            .files()
+            .filter(|p| p.as_str().ends_with(".rcgu.o"))
+            .map(Utf8Path::to_path_buf)
+            .collect();
+        clean::save_temps(&objs);
+
+        self.dep_infos = inv.files().filter_map(dep_info::for_artifact).collect();
+        self.dep_infos.sort_unstable();
+        self.dep_infos.dedup();
+
+        let mut files = vec![];
+        extract_libs(inv.files(), |p, m| {
             if is_alloc_shim(&p) {
                 // Linking the alloc shim causes problems on Linux.
                 log::trace!("Ignoring alloc shim: {p:?}");
                 return;
             }
 
-            log::trace!("Found new rlib: {p:?}");
-            self.latest.push(p);
+            files.push((p, m));
         });
 
-        log::debug!("Found {} new rlibs", self.latest.len());
+        self.latest.clear();
+        for (p, m) in files {
+            let h = self.hashes.get(&p, m)?;
+            if self.initial.get(&p) != Some(&h) {
+                log::trace!("Found new rlib: {p:?}");
+            }
+            self.latest.push((p, h));
+        }
+
+        log::debug!("Found {} rlibs and objects", self.latest.len());
         Ok(())
     }
+
+    /// Start over as if no library was loaded yet, if anything was rolled back
+    /// since the previous build (see `has_changes` and `link_latest`).
+    fn check_rollbacks(&mut self) {
+        let rollbacks = func::rollbacks();
+        if rollbacks == self.rollbacks {
+            return;
+        }
+        self.rollbacks = rollbacks;
+
+        log::debug!("Rolled back since the previous build, the next reload applies all changes");
+        self.hashes.clear();
+        self.loaded.clear();
+        self.linked.clone_from(&self.initial);
+        self.deltas.clear();
+    }
 }
 
-fn init_inner(env: &BuildEnv) -> Result<Builder> {
+fn init_inner(env: &BuildEnv, reload_linker: ReloadLinker) -> Result<Builder> {
     verify_fresh(env).context("Failed to check freshness")?;
 
     let mut cmd = cargo_cmd(env);
+    cmd.arg("--message-format=json-render-diagnostics");
     cmd.args(["--", "--print=link-args"]);
     cmd.arg(format!(
         r#"--cfg=chaud_force_dirty="{}""#,
         current_time_nanos()?
     ));
 
-    let (linker, initial) = extract_linker(cmd).context("Failed to extract linker")?;
+    let mut hashes = HashCache::default();
+    let (linker, initial) = extract_linker(cmd, &mut hashes).context("Failed to extract linker")?;
+    let linker = linker.select(reload_linker);
 
     let mut cmd = cargo_cmd(env);
     cmd.env("__CHAUD_RELOAD", "1");
+    cmd.arg("--message-format=json-render-diagnostics");
     cmd.args([
         "--",
         "--print=link-args",
@@ -85,13 +213,28 @@ fn init_inner(env: &BuildEnv) -> Result<Builder> {
         // that only exist in the running binary (not in the newly compiled
         // code). Using `true` as the linker ensures that the compilation still
         // succeeds (and has the nice side-effect of avoiding unnecessary work).
+        // `chaud-linker` does nothing when `__CHAUD_RELOAD` is set, for the
+        // same reason.
         "-Clinker=true",
         // Ensure that object files for the root crate are kept around (because
         // we don't have an rlib for them).
         "-Csave-temps",
     ]);
 
-    let mut builder = Builder { cmd, linker, initial, latest: vec![] };
+    let mut builder = Builder {
+        cmd,
+        linker,
+        hashes,
+        linked: initial.clone(),
+        loaded: initial.clone(),
+        initial,
+        deltas: vec![],
+        rollbacks: func::rollbacks(),
+        pending: None,
+        latest: vec![],
+        dep_infos: vec![],
+        build_scripts: vec![],
+    };
 
     // Perform an initial build.
     builder.build()?;
@@ -129,19 +272,13 @@ fn verify_fresh(env: &BuildEnv) -> Result<()> {
     Ok(())
 }
 
-fn extract_libs(
-    parts: Vec<String>,
-    initial: &HashMap<Utf8PathBuf, SystemTime>,
+fn extract_libs<'a>(
+    files: impl Iterator<Item = &'a Utf8Path>,
     mut found: impl FnMut(Utf8PathBuf, SystemTime),
 ) {
-    for part in parts {
-        let is_rlib = part.ends_with(".rlib");
-        let is_obj = part.ends_with(".o");
-
-        if !is_rlib && !is_obj {
-            continue;
-        }
-        let part = Utf8PathBuf::from(part);
+    for part in files {
+        let is_obj = part.extension() == Some("o");
+        let part = part.to_path_buf();
 
         match part.metadata() {
             Ok(m) if m.is_file() => {
@@ -153,9 +290,7 @@ fn extract_libs(
                     }
                 };
 
-                if initial.get(&part).is_none_or(|v| *v != mtime) {
-                    found(part, mtime);
-                }
+                found(part, mtime);
             }
             Err(e) if is_obj && e.kind() == io::ErrorKind::NotFound => {
                 log::trace!("Ignoring missing obj {part:?}");
@@ -167,7 +302,14 @@ fn extract_libs(
     }
 }
 
-fn extract_link_args(cmd: &mut Command) -> Result<Vec<String>> {
+/// The output of a `cargo rustc` run with `--message-format=json`.
+struct BuildOutput {
+    /// The linker invocation, unless the build was fully fresh.
+    invocation: Option<Invocation>,
+    build_scripts: Vec<Executed>,
+}
+
+fn run_build(cmd: &mut Command) -> Result<BuildOutput> {
     if log::log_enabled!(log::Level::Trace) {
         log::trace!("Running {cmd:?}");
     } else {
@@ -187,11 +329,23 @@ fn extract_link_args(cmd: &mut Command) -> Result<Vec<String>> {
         start.elapsed().as_secs_f32()
     );
 
-    let output = output?;
-    let output = output.trim();
-    ensure!(!output.contains('\n'), "Too many output lines");
+    // Cargo's messages are JSON, while the linker args printed by `rustc`
+    // (and anything else printed by e.g. a proc-macro) are passed through.
+    let mut build_scripts = vec![];
+    let mut other = String::new();
+    for line in output?.lines() {
+        if let Some(executed) = Executed::parse(line) {
+            build_scripts.push(executed);
+        } else if !line.starts_with('{') {
+            other.push_str(line);
+            other.push('\n');
+        }
+    }
 
-    shlex::split(output).context("shlex failed")
+    Ok(BuildOutput {
+        invocation: Invocation::parse(&other)?,
+        build_scripts,
+    })
 }
 
 fn cargo_cmd(env: &BuildEnv) -> Command {
@@ -211,150 +365,30 @@ fn cargo_cmd(env: &BuildEnv) -> Command {
     cmd
 }
 
-fn extract_linker(mut cmd: Command) -> Result<(Linker, HashMap<Utf8PathBuf, SystemTime>)> {
-    fn skip_out(parts: &mut Peekable<impl Iterator<Item = String>>) -> Result<()> {
-        if parts.peek().is_some_and(|p| p == "-o") {
-            parts.next();
-            let out = parts.next().context("Too short: -o")?;
-            log::trace!("linker: out: {out:?}");
-        }
-        Ok(())
-    }
-
-    let mut has_strip = false;
-    let mut has_no_whole = false;
-    let mut has_whole = false;
-    let mut check_arg = |arg: &str| {
-        has_strip |= arg.contains("--gc-sections") || arg.contains("-dead_strip");
-        has_no_whole |= arg.contains("--no-whole-archive");
-        has_whole |= arg.contains("--whole-archive") || arg.contains("-all_load");
-    };
-
-    let mut parts = extract_link_args(&mut cmd)?.into_iter().peekable();
-
-    let mut env_clear = vec![];
-    if parts.peek().is_some_and(|p| p == "env") {
-        parts.next();
-        while parts.peek().is_some_and(|p| p == "-u") {
-            parts.next();
-            let name = parts.next().context("Too short: -u")?;
-            log::trace!("linker: env_clear: {name:?}");
-            env_clear.push(name);
-        }
-    }
-
-    let mut env_set = vec![];
-    while let Some((k, v)) = parts.peek().and_then(|s| s.split_once('=')) {
-        if !k.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
-            break;
-        }
-        log::trace!("linker: env_set: {k:?} = {v:?}");
-        env_set.push((k.to_owned(), v.to_owned()));
-        parts.next();
-    }
-
-    let linker = parts.next().context("Too short: linker")?;
-    log::trace!("linker: linker: {linker:?}");
-
-    let mut arg_pre = vec![];
-    while parts.peek().is_some_and(|p| !p.ends_with(".o")) {
-        skip_out(&mut parts)?;
-
-        let arg = parts.next().context("unreachable: peeked")?;
-        check_arg(&arg);
-        log::trace!("linker: arg_pre: {arg:?}");
-        arg_pre.push(arg);
-    }
-
-    let mut files = vec![];
-    while parts.peek().is_some_and(|p| p.ends_with(".o")) {
-        let arg = parts.next().context("unreachable: peeked")?;
-        log::trace!("linker: object: {arg:?}");
-        files.push(arg);
-    }
-
-    while parts.peek().is_some_and(|p| !p.ends_with(".rlib")) {
-        skip_out(&mut parts)?;
-
-        let arg = parts.next().context("unreachable: peeked")?;
-        check_arg(&arg);
-        log::trace!("linker: custom: {arg:?}");
-    }
-
-    while parts.peek().is_some_and(|p| p.ends_with(".rlib")) {
-        let arg = parts.next().context("unreachable: peeked")?;
-        log::trace!("linker: rlib: {arg:?}");
-        files.push(arg);
-    }
-
-    let mut arg_post = vec![];
-    while parts.peek().is_some() {
-        skip_out(&mut parts)?;
-
-        let arg = parts.next().context("unreachable: peeked")?;
-        check_arg(&arg);
-        log::trace!("linker: arg_post: {arg:?}");
-        arg_post.push(arg);
-    }
-
-    if has_strip {
-        log::warn!("DEAD CODE CHECK FAILED: `-Clink-dead-code` likely not set");
-    }
-    if has_no_whole {
-        log::warn!(
-            "CUSTOM LINK CHECK FAILED: `--no-whole-archive` detected, likely from custom native linking"
-        );
-    }
-    if !has_whole {
-        log::warn!("LINK ALL CHECK FAILED: `-Zpre-link-args` likely not set properly");
-    }
-
-    let mut initial = HashMap::new();
-    extract_libs(files, &HashMap::new(), |p, m| {
+fn extract_linker(
+    mut cmd: Command,
+    hashes: &mut HashCache,
+) -> Result<(Linker, HashMap<Utf8PathBuf, ContentHash>)> {
+    let inv = run_build(&mut cmd)?
+        .invocation
+        .context("No linker invocation printed")?;
+    let linker = inv.linker()?;
+
+    let mut rlibs = vec![];
+    extract_libs(inv.files(), |p, m| {
         if p.as_str().ends_with(".rlib") {
-            initial.insert(p, m);
+            rlibs.push((p, m));
         }
     });
-    log::debug!("Found {} initial rlibs", initial.len());
-
-    Ok((
-        Linker {
-            env_clear: env_clear.into_boxed_slice(),
-            env_set: env_set.into_boxed_slice(),
-            bin: linker,
-            arg_pre: arg_pre.into_boxed_slice(),
-            arg_post: arg_post.into_boxed_slice(),
-        },
-        initial,
-    ))
-}
-
-fn link(dst: &Utf8Path, linker: &Linker, latest: &[Utf8PathBuf]) -> Result<()> {
-    let mut cmd = Command::new(&linker.bin);
 
-    for (k, v) in &linker.env_set {
-        cmd.env(k, v);
-    }
-    for k in &linker.env_clear {
-        cmd.env_remove(k);
-    }
-
-    cmd.args(&linker.arg_pre)
-        .args(latest)
-        .args(&linker.arg_post);
-
-    if cfg!(target_os = "macos") {
-        cmd.args(["-undefined", "dynamic_lookup"]);
+    let mut initial = HashMap::new();
+    for (p, m) in rlibs {
+        let h = hashes.get(&p, m)?;
+        initial.insert(p, h);
     }
+    log::debug!("Found {} initial rlibs", initial.len());
 
-    cmd.args(["-shared", "-o", dst.as_str()]);
-
-    log::trace!("Executing: {cmd:?}");
-
-    let st = cmd.status()?;
-    ensure!(st.success(), "Linking failed: {st}");
-
-    Ok(())
+    Ok((linker, initial))
 }
 
 fn current_time_nanos() -> Result<u128> {
@@ -0,0 +1,103 @@
+//! Parsing of the Makefile-style dep-info (`.d`) files written by `rustc`.
+
+use crate::util::normalize;
+use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::fs;
+
+/// The dep-info file `rustc` writes for the artifact `part` (an rlib or an
+/// object file from `-Csave-temps`), in the same directory.
+pub fn for_artifact(part: &Utf8Path) -> Option<Utf8PathBuf> {
+    let name = part.file_name()?;
+
+    let stem = match name.strip_suffix(".rlib") {
+        // `lib<crate>-<hash>.rlib`
+        Some(stem) => stem.strip_prefix("lib")?,
+        // `<crate>-<hash>.<crate>.<cgu>.rcgu.o`
+        None => name.strip_suffix(".rcgu.o")?.split('.').next()?,
+    };
+
+    Some(part.with_file_name(format!("{stem}.d")))
+}
+
+/// The crate name encoded in the name of the dep-info file `path`.
+pub fn crate_name(path: &Utf8Path) -> Option<&str> {
+    Some(path.file_stem()?.rsplit_once('-')?.0)
+}
+
+/// Parse the dep-info file at `path`, returning all the input files it lists.
+///
+/// Relative paths are resolved against `base`, the directory `rustc` was run
+/// in (i.e., the workspace root).
+pub fn parse(path: &Utf8Path, base: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    let buf = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+
+    let mut inputs = vec![];
+    for line in buf.lines() {
+        // Comments contain, e.g., `env-dep`s.
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let Some((_, deps)) = split_rule(line) else {
+            continue;
+        };
+
+        for dep in split_deps(deps) {
+            inputs.push(normalize(&base.join(dep)));
+        }
+    }
+
+    inputs.sort_unstable();
+    inputs.dedup();
+    Ok(inputs)
+}
+
+/// Split `target: deps` at the first unescaped `:` that is followed by a space
+/// or the end of the line.
+fn split_rule(line: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (pos, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ':' => {
+                let rest = line.get(pos + 1..)?;
+                if rest.is_empty() || rest.starts_with(' ') {
+                    return Some((line.get(..pos)?, rest));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split the space-separated `deps`, unescaping `\ `.
+fn split_deps(deps: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut cur = String::new();
+    let mut chars = deps.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(' ') => cur.push(' '),
+                Some(c) => {
+                    cur.push('\\');
+                    cur.push(c);
+                }
+                None => cur.push('\\'),
+            },
+            ' ' => {
+                if !cur.is_empty() {
+                    out.push(core::mem::take(&mut cur));
+                }
+            }
+            c => cur.push(c),
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
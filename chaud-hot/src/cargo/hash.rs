@@ -0,0 +1,73 @@
+//! Content **hash**es of build artifacts, to detect which of them changed.
+
+use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use core::hash::{BuildHasher as _, Hasher as _};
+use foldhash::quality::FixedState;
+use hashbrown::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+/// The hash of the contents of a file.
+pub type ContentHash = u64;
+
+/// Content hashes of files, cached by path and mtime.
+///
+/// Cargo may rebuild a crate without changing its output (e.g. after a comment
+/// was edited), and tools may `touch` files. Comparing hashes instead of mtimes
+/// avoids relinking and reloading in those cases.
+#[derive(Default)]
+pub struct HashCache {
+    cache: HashMap<Utf8PathBuf, (SystemTime, ContentHash)>,
+}
+
+impl HashCache {
+    /// Get the hash of the file at `path`, which was last modified at `mtime`.
+    pub fn get(&mut self, path: &Utf8Path, mtime: SystemTime) -> Result<ContentHash> {
+        if let Some(&(m, h)) = self.cache.get(path) {
+            if m == mtime {
+                return Ok(h);
+            }
+        }
+
+        let buf = fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+        let mut hasher = FixedState::with_seed(0).build_hasher();
+        hasher.write(&buf);
+        let hash = hasher.finish();
+
+        log::trace!("Hashed {path:?}: {hash:016x}");
+        self.cache.insert(path.to_owned(), (mtime, hash));
+        Ok(hash)
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp::TempDir;
+    use core::time::Duration;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn content() -> Result<()> {
+        let dir = TempDir::new("hash")?;
+        let path = dir.join("lib.rlib");
+
+        let mut hashes = HashCache::default();
+        let mut hash = |contents: &str, secs| {
+            fs::write(&path, contents)?;
+            hashes.get(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        };
+
+        let a = hash("a", 1)?;
+        // The same contents with a new mtime are unchanged, and different
+        // contents are changed.
+        assert_eq!(hash("a", 2)?, a);
+        assert_ne!(hash("b", 3)?, a);
+        Ok(())
+    }
+}
@@ -0,0 +1,776 @@
+//! The **linker** invocation of the root binary, as printed by
+//! `rustc --print=link-args`, which is reused to link hot-reloaded libraries.
+
+use crate::ReloadLinker;
+use anyhow::{Context as _, Result, ensure};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::ffi::OsString;
+use std::process::Command;
+use std::{env, fs};
+
+/// How deeply response files may reference other response files.
+const MAX_RESPONSE_DEPTH: usize = 8;
+
+/// Flags whose value is passed as a separate argument, which must not be
+/// mistaken for an input file.
+const FLAGS_WITH_VALUE: &[&str] = &[
+    "-L",
+    "-T",
+    "-Xlinker",
+    "-arch",
+    "-e",
+    "-framework",
+    "-install_name",
+    "-isysroot",
+    "-l",
+    "-m",
+    "-rpath",
+    "-soname",
+    "-syslibroot",
+    "-target",
+    "-u",
+    "-z",
+];
+
+/// Linkers that are invoked directly (instead of via a compiler driver like
+/// `cc`), and accept the same arguments.
+const GNU_LINKERS: &[&str] = &["ld", "ld.bfd", "ld.gold", "ld.lld", "ld.mold", "mold"];
+
+/// A parsed linker invocation.
+#[derive(Debug)]
+pub struct Invocation {
+    env_clear: Vec<String>,
+    env_set: Vec<(String, String)>,
+    bin: String,
+    args: Vec<Arg>,
+}
+
+/// A classified argument of an [`Invocation`].
+#[derive(Debug, PartialEq, Eq)]
+enum Arg {
+    /// An object file of the root crate. These precede the rlibs.
+    Object(Utf8PathBuf),
+    /// An rlib of a dependency.
+    Rlib(Utf8PathBuf),
+    /// The output file, `-o <path>`.
+    Output(String),
+    /// Any other argument, including the value of [`FLAGS_WITH_VALUE`].
+    Other(Box<[String]>),
+}
+
+/// The linker invocation without its input and output files.
+#[derive(Clone)]
+pub struct Linker {
+    env_clear: Box<[String]>,
+    env_set: Box<[(String, String)]>,
+    bin: String,
+    /// Arguments before the first input file.
+    arg_pre: Box<[String]>,
+    /// Arguments after the last input file.
+    arg_post: Box<[String]>,
+    /// The original linker, to retry with if linking fails.
+    fallback: Option<Box<Linker>>,
+}
+
+impl Invocation {
+    /// Parse the stdout of a build with `--print=link-args`.
+    ///
+    /// Returns `None` if there is no output, i.e., if the binary was fresh.
+    pub fn parse(stdout: &str) -> Result<Option<Self>> {
+        // The linker invocation is printed last. Anything before it was printed
+        // by something else, e.g. a proc-macro.
+        let mut lines = stdout.lines().filter(|l| !l.trim().is_empty());
+        let Some(line) = lines.next_back() else {
+            return Ok(None);
+        };
+        for line in lines {
+            log::debug!("Ignoring unexpected output: {line:?}");
+        }
+
+        let parts = shlex::split(line).context("shlex failed")?;
+        Self::from_parts(parts).map(Some)
+    }
+
+    fn from_parts(parts: Vec<String>) -> Result<Self> {
+        let mut parts = parts.into_iter().peekable();
+
+        let mut env_clear = vec![];
+        if parts.next_if_eq("env").is_some() {
+            while parts.next_if_eq("-u").is_some() {
+                let name = parts.next().context("Too short: -u")?;
+                log::trace!("linker: env_clear: {name:?}");
+                env_clear.push(name);
+            }
+        }
+
+        let mut env_set = vec![];
+        while let Some(part) = parts.next_if(|p| is_env_assignment(p)) {
+            let Some((k, v)) = part.split_once('=') else {
+                continue;
+            };
+            log::trace!("linker: env_set: {k:?} = {v:?}");
+            env_set.push((k.to_owned(), v.to_owned()));
+        }
+
+        let bin = parts.next().context("Too short: linker")?;
+        log::trace!("linker: linker: {bin:?}");
+
+        let mut expanded = vec![];
+        expand(parts, &mut expanded, 0)?;
+
+        Ok(Self { env_clear, env_set, bin, args: classify(expanded)? })
+    }
+
+    /// The object files and rlibs passed to the linker, in order.
+    pub fn files(&self) -> impl Iterator<Item = &Utf8Path> {
+        self.args.iter().filter_map(|a| match a {
+            Arg::Object(p) | Arg::Rlib(p) => Some(p.as_path()),
+            Arg::Output(_) | Arg::Other(_) => None,
+        })
+    }
+
+    /// The invocation without its input and output files.
+    ///
+    /// Arguments between the first and the last input file (e.g. native
+    /// libraries of dependencies) are dropped, since they are already linked
+    /// into the binary.
+    pub fn linker(&self) -> Result<Linker> {
+        let is_file = |a: &Arg| matches!(a, Arg::Object(_) | Arg::Rlib(_));
+        let first = self
+            .args
+            .iter()
+            .position(is_file)
+            .context("No object files or rlibs")?;
+        let last = self.args.iter().rposition(is_file).unwrap_or(first);
+
+        let mut checks = Checks::default();
+        let mut arg_pre = vec![];
+        let mut arg_post = vec![];
+        for (idx, arg) in self.args.iter().enumerate() {
+            let args = match arg {
+                Arg::Object(p) => {
+                    log::trace!("linker: object: {p:?}");
+                    continue;
+                }
+                Arg::Rlib(p) => {
+                    log::trace!("linker: rlib: {p:?}");
+                    continue;
+                }
+                Arg::Output(out) => {
+                    log::trace!("linker: out: {out:?}");
+                    continue;
+                }
+                Arg::Other(args) => args,
+            };
+
+            for arg in args {
+                checks.check(arg);
+            }
+
+            if idx < first {
+                log::trace!("linker: arg_pre: {args:?}");
+                arg_pre.extend(args.iter().cloned());
+            } else if idx > last {
+                log::trace!("linker: arg_post: {args:?}");
+                arg_post.extend(args.iter().cloned());
+            } else {
+                log::trace!("linker: custom: {args:?}");
+            }
+        }
+
+        // `chaud-linker` includes all symbols itself.
+        checks.has_whole |= Utf8Path::new(&self.bin).file_stem() == Some("chaud-linker");
+        checks.report();
+
+        Ok(Linker {
+            env_clear: self.env_clear.clone().into_boxed_slice(),
+            env_set: self.env_set.clone().into_boxed_slice(),
+            bin: self.bin.clone(),
+            arg_pre: arg_pre.into_boxed_slice(),
+            arg_post: arg_post.into_boxed_slice(),
+            fallback: None,
+        })
+    }
+}
+
+/// A linker that is usually faster than the default.
+#[derive(Debug, Clone, Copy)]
+enum FastLinker {
+    Lld,
+    Mold,
+}
+
+impl FastLinker {
+    fn bin(self) -> &'static str {
+        match self {
+            Self::Lld => "ld.lld",
+            Self::Mold => "mold",
+        }
+    }
+
+    fn fuse_ld(self) -> &'static str {
+        match self {
+            Self::Lld => "-fuse-ld=lld",
+            Self::Mold => "-fuse-ld=mold",
+        }
+    }
+}
+
+impl Linker {
+    /// Link hot-reloaded libraries with `choice` instead of the original
+    /// linker, where applicable.
+    #[must_use]
+    pub fn select(mut self, choice: ReloadLinker) -> Self {
+        let fast = match choice {
+            ReloadLinker::Original => return self,
+            ReloadLinker::Lld => FastLinker::Lld,
+            ReloadLinker::Mold => FastLinker::Mold,
+            ReloadLinker::Auto => {
+                if !cfg!(target_os = "linux") || self.is_fast() {
+                    return self;
+                }
+                let Some(fast) = [FastLinker::Mold, FastLinker::Lld]
+                    .into_iter()
+                    .find(|f| self.in_path(f.bin()))
+                else {
+                    log::debug!("Neither `mold` nor `ld.lld` found, using original linker");
+                    return self;
+                };
+                self.fallback = Some(Box::new(self.clone()));
+                fast
+            }
+        };
+
+        if GNU_LINKERS.contains(&self.bin_name()) {
+            fast.bin().clone_into(&mut self.bin);
+        } else {
+            // A compiler driver, which selects the linker via `-fuse-ld`.
+            let is_fuse_ld = |a: &String| a.starts_with("-fuse-ld=");
+            self.arg_pre = self
+                .arg_pre
+                .into_iter()
+                .filter(|a| !is_fuse_ld(a))
+                .collect();
+            self.arg_post = self
+                .arg_post
+                .into_iter()
+                .filter(|a| !is_fuse_ld(a))
+                .chain([fast.fuse_ld().to_owned()])
+                .collect();
+        }
+
+        log::info!("Linking hot-reloaded libraries with `{}`", fast.bin());
+        self
+    }
+
+    fn bin_name(&self) -> &str {
+        Utf8Path::new(&self.bin).file_name().unwrap_or(&self.bin)
+    }
+
+    /// Whether the original linker is already `lld` or `mold`.
+    fn is_fast(&self) -> bool {
+        let fast_bins = ["ld.lld", "ld.mold", "mold", "rust-lld"];
+        let fast_args = ["-fuse-ld=lld", "-fuse-ld=mold"];
+        fast_bins.contains(&self.bin_name())
+            || self
+                .arg_pre
+                .iter()
+                .chain(&self.arg_post)
+                .any(|a| fast_args.contains(&a.as_str()))
+    }
+
+    /// Whether `name` is found in the `PATH` the linker is invoked with.
+    fn in_path(&self, name: &str) -> bool {
+        let path = self
+            .env_set
+            .iter()
+            .find(|(k, _)| k == "PATH")
+            .map(|(_, v)| OsString::from(v))
+            .or_else(|| env::var_os("PATH"));
+
+        path.is_some_and(|p| env::split_paths(&p).any(|d| d.join(name).is_file()))
+    }
+
+    /// Link `files` into the shared library `dst`, which depends on the
+    /// shared libraries `deps`.
+    ///
+    /// If this fails with an automatically selected linker, it is retried
+    /// once with the original linker.
+    pub fn link<'a>(
+        &self,
+        dst: &Utf8Path,
+        files: &[Utf8PathBuf],
+        deps: impl IntoIterator<Item = &'a Utf8PathBuf>,
+    ) -> Result<()> {
+        let deps: Vec<_> = deps.into_iter().collect();
+        let res = self.link_once(dst, files, &deps);
+
+        match (res, &self.fallback) {
+            (Err(e), Some(original)) => {
+                log::warn!(
+                    "{e:#}, retrying with the original linker `{}`",
+                    original.bin
+                );
+                original.link_once(dst, files, &deps)
+            }
+            (res, _) => res,
+        }
+    }
+
+    fn link_once(
+        &self,
+        dst: &Utf8Path,
+        files: &[Utf8PathBuf],
+        deps: &[&Utf8PathBuf],
+    ) -> Result<()> {
+        let mut cmd = Command::new(&self.bin);
+
+        for (k, v) in &self.env_set {
+            cmd.env(k, v);
+        }
+        for k in &self.env_clear {
+            cmd.env_remove(k);
+        }
+
+        cmd.args(&self.arg_pre)
+            .args(files)
+            .args(deps)
+            .args(&self.arg_post);
+
+        if cfg!(target_os = "macos") {
+            cmd.args(["-undefined", "dynamic_lookup"]);
+        }
+
+        cmd.args(["-shared", "-o", dst.as_str()]);
+
+        log::trace!("Executing: {cmd:?}");
+
+        let st = cmd.status()?;
+        ensure!(st.success(), "Linking failed: {st}");
+
+        Ok(())
+    }
+}
+
+/// Sanity checks of the flags the binary was linked with.
+#[derive(Default)]
+struct Checks {
+    has_strip: bool,
+    has_no_whole: bool,
+    has_whole: bool,
+}
+
+impl Checks {
+    fn check(&mut self, arg: &str) {
+        self.has_strip |= arg.contains("--gc-sections") || arg.contains("-dead_strip");
+        self.has_no_whole |= arg.contains("--no-whole-archive");
+        self.has_whole |= arg.contains("--whole-archive") || arg.contains("-all_load");
+    }
+
+    fn report(&self) {
+        if self.has_strip {
+            log::warn!("DEAD CODE CHECK FAILED: `-Clink-dead-code` likely not set");
+        }
+        if self.has_no_whole {
+            log::warn!(
+                "CUSTOM LINK CHECK FAILED: `--no-whole-archive` detected, likely from custom native linking"
+            );
+        }
+        if !self.has_whole {
+            log::warn!("LINK ALL CHECK FAILED: `-Clinker=chaud-linker` likely not set properly");
+        }
+    }
+}
+
+fn is_env_assignment(part: &str) -> bool {
+    let Some((k, _)) = part.split_once('=') else {
+        return false;
+    };
+    let mut chars = k.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace response files (`@<path>`) in `args` with their contents.
+fn expand(
+    args: impl IntoIterator<Item = String>,
+    out: &mut Vec<String>,
+    depth: usize,
+) -> Result<()> {
+    for arg in args {
+        let Some(path) = arg.strip_prefix('@') else {
+            out.push(arg);
+            continue;
+        };
+
+        ensure!(
+            depth < MAX_RESPONSE_DEPTH,
+            "Response files nested too deeply: {path:?}"
+        );
+
+        log::trace!("linker: response file: {path:?}");
+        let buf = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read response file {path:?}"))?;
+        expand(split_response(&buf), out, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Split the contents of a response file like GCC and LLD do: Arguments are
+/// separated by whitespace, which can be escaped with `\` or quoted with `'` or
+/// `"`.
+fn split_response(buf: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut cur = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+
+    let mut chars = buf.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    cur.push(c);
+                }
+                in_arg = true;
+            }
+            c if quote == Some(c) => quote = None,
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            c if c.is_whitespace() && quote.is_none() => {
+                if in_arg {
+                    out.push(core::mem::take(&mut cur));
+                    in_arg = false;
+                }
+            }
+            c => {
+                cur.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        out.push(cur);
+    }
+    out
+}
+
+fn classify(args: Vec<String>) -> Result<Vec<Arg>> {
+    let mut args = args.into_iter();
+    let mut out = vec![];
+    let mut seen_rlib = false;
+
+    while let Some(arg) = args.next() {
+        let classified = if arg == "-o" {
+            Arg::Output(args.next().context("Too short: -o")?)
+        } else if FLAGS_WITH_VALUE.contains(&arg.as_str()) {
+            let value = args.next().with_context(|| format!("Too short: {arg}"))?;
+            Arg::Other([arg, value].into())
+        } else if arg.ends_with(".rlib") {
+            seen_rlib = true;
+            Arg::Rlib(arg.into())
+        } else if arg.ends_with(".o") && !seen_rlib {
+            Arg::Object(arg.into())
+        } else {
+            // Object files after the rlibs were added by custom link args.
+            Arg::Other([arg].into())
+        };
+        out.push(classified);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp::TempDir;
+    use pretty_assertions::assert_eq;
+
+    /// Recorded with `cargo rustc -- --print=link-args` for a binary with a
+    /// single dependency, with the paths shortened.
+    const GCC: &str = include_str!("../../testdata/link-args/gcc.txt");
+    /// As [`GCC`], with `-Clinker=clang`.
+    const CLANG: &str = include_str!("../../testdata/link-args/clang.txt");
+    /// As [`GCC`], with `-Clinker=ld.lld`.
+    const LLD: &str = include_str!("../../testdata/link-args/lld.txt");
+    /// As [`GCC`], with `-Clink-arg=-fuse-ld=mold`,
+    /// `-Clink-arg=-Wl,--export-dynamic` and `-lstatic=foo`.
+    const MOLD: &str = include_str!("../../testdata/link-args/mold.txt");
+
+    fn parse(stdout: &str) -> Result<Invocation> {
+        Invocation::parse(stdout)?.context("No invocation")
+    }
+
+    fn strs(args: &[String]) -> Vec<&str> {
+        args.iter().map(String::as_str).collect()
+    }
+
+    fn file_names(inv: &Invocation) -> Vec<&str> {
+        inv.files().filter_map(|f| f.file_name()).collect()
+    }
+
+    fn check_recorded(stdout: &str, bin: &str) -> Result<Invocation> {
+        let inv = parse(stdout)?;
+        assert_eq!(inv.bin, bin);
+        assert_eq!(inv.env_clear, Vec::<String>::new());
+
+        let keys: Vec<_> = inv.env_set.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["LC_ALL", "PATH", "VSLANG"]);
+
+        let files = file_names(&inv);
+        assert_eq!(files.len(), 29);
+        assert_eq!(files.first(), Some(&"symbols.o"));
+        assert_eq!(
+            files.get(9),
+            Some(&"libdep-ebc9e1cba77036ea.rlib"),
+            "first rlib"
+        );
+        assert_eq!(
+            files.last(),
+            Some(&"libcompiler_builtins-27cfc16bdf3bb694.rlib")
+        );
+
+        let objs = inv
+            .args
+            .iter()
+            .filter(|a| matches!(a, Arg::Object(_)))
+            .count();
+        assert_eq!(objs, 9);
+
+        Ok(inv)
+    }
+
+    #[test]
+    fn gcc() -> Result<()> {
+        let inv = check_recorded(GCC, "cc")?;
+        let linker = inv.linker()?;
+        assert_eq!(strs(&linker.arg_pre), ["-m64"]);
+        assert_eq!(
+            strs(&linker.arg_post),
+            [
+                "-Wl,-Bdynamic",
+                "-lgcc_s",
+                "-lutil",
+                "-lrt",
+                "-lpthread",
+                "-lm",
+                "-ldl",
+                "-lc",
+                "-L",
+                "<app>/target/debug/deps/rustcepfyPf/raw-dylibs",
+                "-B<sysroot>/lib/rustlib/x86_64-unknown-linux-gnu/bin/gcc-ld",
+                "-fuse-ld=lld",
+                "-Wl,--eh-frame-hdr",
+                "-Wl,-z,noexecstack",
+                "-L",
+                "<sysroot>/lib/rustlib/x86_64-unknown-linux-gnu/lib",
+                "-Wl,--gc-sections",
+                "-pie",
+                "-Wl,-z,relro,-z,now",
+                "-nodefaultlibs",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clang() -> Result<()> {
+        let inv = check_recorded(CLANG, "clang")?;
+        let linker = inv.linker()?;
+        assert_eq!(strs(&linker.arg_pre), ["-m64"]);
+        assert!(!linker.arg_post.iter().any(|a| a == "-o"));
+        Ok(())
+    }
+
+    #[test]
+    fn lld() -> Result<()> {
+        let inv = check_recorded(LLD, "ld.lld")?;
+        let linker = inv.linker()?;
+        assert_eq!(strs(&linker.arg_pre), Vec::<&str>::new());
+        assert_eq!(
+            linker.arg_post.get(10..),
+            Some(
+                &[
+                    "--eh-frame-hdr",
+                    "-z",
+                    "noexecstack",
+                    "-L",
+                    "<sysroot>/lib/rustlib/x86_64-unknown-linux-gnu/lib",
+                    "--gc-sections",
+                    "-pie",
+                    "-z",
+                    "relro",
+                    "-z",
+                    "now",
+                ]
+                .map(String::from)[..]
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mold() -> Result<()> {
+        let inv = check_recorded(MOLD, "cc")?;
+        let linker = inv.linker()?;
+        assert_eq!(strs(&linker.arg_pre), ["-m64"]);
+        // The native library is linked between the objects and the rlibs.
+        assert!(!linker.arg_post.iter().any(|a| a == "-lfoo"));
+        assert_eq!(
+            linker.arg_post.get(linker.arg_post.len() - 2..),
+            Some(&["-fuse-ld=mold", "-Wl,--export-dynamic"].map(String::from)[..])
+        );
+        Ok(())
+    }
+
+    fn fuse_ld(linker: &Linker) -> Vec<&str> {
+        linker
+            .arg_pre
+            .iter()
+            .chain(&linker.arg_post)
+            .filter(|a| a.starts_with("-fuse-ld="))
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn select_driver() -> Result<()> {
+        let linker = parse(GCC)?.linker()?;
+        assert!(linker.is_fast());
+
+        let linker = linker.select(ReloadLinker::Auto);
+        assert_eq!(fuse_ld(&linker), ["-fuse-ld=lld"]);
+        assert!(linker.fallback.is_none());
+
+        let linker = linker.select(ReloadLinker::Mold);
+        assert_eq!(linker.bin, "cc");
+        assert_eq!(fuse_ld(&linker), ["-fuse-ld=mold"]);
+
+        let linker = parse(CLANG)?.linker()?;
+        assert!(!linker.is_fast());
+
+        let linker = linker.select(ReloadLinker::Original);
+        assert_eq!(fuse_ld(&linker), Vec::<&str>::new());
+
+        let linker = linker.select(ReloadLinker::Lld);
+        assert_eq!(linker.bin, "clang");
+        assert_eq!(fuse_ld(&linker), ["-fuse-ld=lld"]);
+        Ok(())
+    }
+
+    #[test]
+    fn select_direct() -> Result<()> {
+        let linker = parse(LLD)?.linker()?;
+        assert!(linker.is_fast());
+
+        let linker = linker.select(ReloadLinker::Mold);
+        assert_eq!(linker.bin, "mold");
+        assert_eq!(fuse_ld(&linker), Vec::<&str>::new());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn auto_fallback() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = TempDir::new("fallback")?;
+
+        // A compiler driver that fails to link with `mold`.
+        let cc = dir.join("cc");
+        fs::write(
+            &cc,
+            "#!/bin/sh\ncase \"$*\" in *-fuse-ld=mold*) exit 1;; esac\n",
+        )?;
+        fs::set_permissions(&cc, fs::Permissions::from_mode(0o755))?;
+        fs::write(dir.join("mold"), "")?;
+
+        let inv = parse(&format!(r#"env PATH="{}" "{cc}" "a.o""#, dir.as_str()))?;
+        let dst = dir.join("out.so");
+
+        let auto = inv.linker()?.select(ReloadLinker::Auto);
+        let mold = inv.linker()?.select(ReloadLinker::Mold);
+        assert_eq!(fuse_ld(&auto), ["-fuse-ld=mold"]);
+        auto.link(&dst, &[], [])?;
+        assert!(mold.link(&dst, &[], []).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn env() -> Result<()> {
+        let inv = parse(r#"env -u FOO -u BAR X86_64_VAR="a b" "cc" "a.o" "liba.rlib""#)?;
+        assert_eq!(inv.env_clear, ["FOO", "BAR"]);
+        assert_eq!(inv.env_set, [("X86_64_VAR".to_owned(), "a b".to_owned())]);
+        assert_eq!(inv.bin, "cc");
+        assert_eq!(file_names(&inv), ["a.o", "liba.rlib"]);
+        Ok(())
+    }
+
+    #[test]
+    fn unusual_layout() -> Result<()> {
+        let inv = parse(concat!(
+            r#""cc" "-Xlinker" "x.o" "a.rcgu.o" "-lfoo" "b.rcgu.o" "liba.rlib" "#,
+            r#""-l" "bar" "-Wl,-Bstatic" "libb.rlib" "-o" "out" "extra.o" "-lc""#
+        ))?;
+        assert_eq!(
+            file_names(&inv),
+            ["a.rcgu.o", "b.rcgu.o", "liba.rlib", "libb.rlib"]
+        );
+
+        let linker = inv.linker()?;
+        assert_eq!(strs(&linker.arg_pre), ["-Xlinker", "x.o"]);
+        assert_eq!(strs(&linker.arg_post), ["extra.o", "-lc"]);
+        Ok(())
+    }
+
+    #[test]
+    fn output() -> Result<()> {
+        assert!(Invocation::parse("")?.is_none());
+        assert!(Invocation::parse("\n  \n")?.is_none());
+
+        let inv = parse("printed by a proc-macro\n\"cc\" \"a.o\"\n")?;
+        assert_eq!(file_names(&inv), ["a.o"]);
+        Ok(())
+    }
+
+    #[test]
+    fn response_files() -> Result<()> {
+        let dir = TempDir::new("linker")?;
+
+        let inner = dir.join("inner");
+        fs::write(&inner, "'libc d.rlib' -lc\n")?;
+
+        let outer = dir.join("outer");
+        fs::write(
+            &outer,
+            format!("-m64\n\"a b.o\"\nlib\\ a.rlib\n\n  @{inner}  -Wl,-z,now\n"),
+        )?;
+
+        let inv = parse(&format!(r#""cc" "@{outer}" "-o" "out""#))?;
+
+        assert_eq!(file_names(&inv), ["a b.o", "lib a.rlib", "libc d.rlib"]);
+
+        let linker = inv.linker()?;
+        assert_eq!(strs(&linker.arg_pre), ["-m64"]);
+        assert_eq!(strs(&linker.arg_post), ["-lc", "-Wl,-z,now"]);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_response_file() {
+        assert!(parse(r#""cc" "@/nonexistent/chaud/response" "a.o""#).is_err());
+    }
+
+    #[test]
+    fn response_syntax() {
+        assert_eq!(
+            split_response(r#"a "b c" 'd "e"' f\ g "h\"i" '' j"#),
+            ["a", "b c", "d \"e\"", "f g", "h\"i", "", "j"]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! The `[package.metadata.chaud]` table of a manifest.
+#![expect(clippy::question_mark, reason = "generated by `DeJson` for `Option`s")]
+
+use crate::{Config, ReloadLinker};
+use core::str::Chars;
+use core::time::Duration;
+use nanoserde::{DeJson, DeJsonErr, DeJsonState, DeJsonTok};
+
+/// The `metadata` of a package, which is `null` if the manifest has no
+/// `[package.metadata]` table.
+#[derive(Debug, Default)]
+pub struct PackageMetadata {
+    chaud: Option<ManifestConfig>,
+}
+
+#[derive(Debug, DeJson)]
+struct Tables {
+    chaud: Option<ManifestConfig>,
+}
+
+#[derive(Debug, DeJson, Clone)]
+struct ManifestConfig {
+    #[nserde(rename = "debounce-ms")]
+    debounce_ms: Option<u64>,
+    #[nserde(rename = "poll-ms")]
+    poll_ms: Option<u64>,
+    watch: Option<Vec<String>>,
+    ignore: Option<Vec<String>>,
+    assets: Option<Vec<String>>,
+    packages: Option<Vec<String>>,
+    #[nserde(rename = "cargo-args")]
+    cargo_args: Option<Vec<String>>,
+    cargo: Option<String>,
+    profile: Option<String>,
+    target: Option<String>,
+    trigger: Option<String>,
+    #[nserde(rename = "reload-signal")]
+    reload_signal: Option<String>,
+    #[nserde(rename = "reload-linker")]
+    reload_linker: Option<String>,
+}
+
+impl DeJson for PackageMetadata {
+    fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Self, DeJsonErr> {
+        if s.tok == DeJsonTok::Null {
+            s.next_tok(i)?;
+            return Ok(Self::default());
+        }
+
+        let Tables { chaud } = Tables::de_json(s, i)?;
+        Ok(Self { chaud })
+    }
+}
+
+impl PackageMetadata {
+    pub fn config(&self) -> Config {
+        let Some(c) = self.chaud.clone() else {
+            return Config::default();
+        };
+
+        let manual = match c.trigger.as_deref() {
+            None => None,
+            Some("auto") => Some(false),
+            Some("manual") => Some(true),
+            Some(other) => {
+                log::warn!("Ignoring unknown trigger in manifest: {other:?}");
+                None
+            }
+        };
+
+        let reload_linker = match c.reload_linker.as_deref() {
+            None => None,
+            Some("auto") => Some(ReloadLinker::Auto),
+            Some("original") => Some(ReloadLinker::Original),
+            Some("lld") => Some(ReloadLinker::Lld),
+            Some("mold") => Some(ReloadLinker::Mold),
+            Some(other) => {
+                log::warn!("Ignoring unknown reload linker in manifest: {other:?}");
+                None
+            }
+        };
+
+        Config {
+            debounce: c.debounce_ms.map(Duration::from_millis),
+            poll: c.poll_ms.map(Duration::from_millis),
+            watch: c
+                .watch
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            ignore: c.ignore.unwrap_or_default(),
+            assets: c.assets.unwrap_or_default(),
+            packages: c.packages.unwrap_or_default(),
+            cargo_args: c.cargo_args.unwrap_or_default(),
+            cargo: c.cargo.map(Into::into),
+            profile: c.profile,
+            target: c.target,
+            manual,
+            reload_signal: c.reload_signal,
+            reload_linker,
+            minilog: None,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use super::Cargo;
+use super::manifest::PackageMetadata;
+use crate::Config;
 use crate::cargo::StdioMode;
 use crate::util::CommandExt as _;
 use anyhow::{Context as _, Result};
@@ -10,11 +12,17 @@ use nanoserde::{DeJson, DeJsonErr, DeJsonState, DeJsonTok};
 #[derive(Debug, DeJson)]
 pub struct Metadata {
     packages: Vec<Package>,
+    resolve: Resolve,
+    #[nserde(proxy = "String")]
+    workspace_root: Utf8PathBuf,
+    #[nserde(proxy = "String")]
+    target_directory: Utf8PathBuf,
 }
 
 impl Cargo {
-    pub fn load_metadata(&self) -> Result<Metadata> {
-        let buf = run_cargo(self).context("Failed to run `cargo metadata`")?;
+    /// Run `cargo metadata` with the additional `args`.
+    pub fn load_metadata<'a>(&self, args: impl IntoIterator<Item = &'a str>) -> Result<Metadata> {
+        let buf = run_cargo(self, args).context("Failed to run `cargo metadata`")?;
         Metadata::deserialize_json(&buf).context("Failed to parse `cargo metadata` output")
     }
 }
@@ -23,13 +31,28 @@ impl Metadata {
     pub fn packages(&self) -> &[Package] {
         &self.packages
     }
+
+    /// The resolved dependency graph, which is empty with `--no-deps`.
+    pub fn resolve(&self) -> &[Node] {
+        &self.resolve.nodes
+    }
+
+    pub fn workspace_root(&self) -> &Utf8Path {
+        &self.workspace_root
+    }
+
+    pub fn target_directory(&self) -> &Utf8Path {
+        &self.target_directory
+    }
 }
 #[derive(Debug, DeJson)]
 pub struct Package {
     name: PackageName,
+    id: PackageId,
+    source: PackageSource,
     manifest_path: ManifestPath,
-    dependencies: Vec<Dependency>,
     targets: Vec<Target>,
+    metadata: PackageMetadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -57,49 +80,142 @@ impl Package {
         &self.name
     }
 
-    pub fn manifest_path(&self) -> &ManifestPath {
-        &self.manifest_path
+    pub fn id(&self) -> &PackageId {
+        &self.id
+    }
+
+    /// Whether the package is a workspace member or a path dependency (as
+    /// opposed to e.g. a registry or git dependency).
+    pub fn is_path(&self) -> bool {
+        self.source == PackageSource::Path
     }
 
-    pub fn dependencies(&self) -> &[Dependency] {
-        &self.dependencies
+    pub fn manifest_path(&self) -> &ManifestPath {
+        &self.manifest_path
     }
 
     pub fn targets(&self) -> &[Target] {
         &self.targets
     }
+
+    /// The configuration from the `[package.metadata.chaud]` table.
+    pub fn config(&self) -> Config {
+        self.metadata.config()
+    }
 }
 
 #[derive(Debug, DeJson, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[nserde(transparent)]
 pub struct PackageName(String);
 
+impl PackageName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl fmt::Display for PackageName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "`{}`", self.0)
     }
 }
 
+/// Where a package comes from. Only path packages (including workspace
+/// members) are distinguished.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum PackageSource {
+    Path,
+    Other,
+}
+
+impl DeJson for PackageSource {
+    fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Self, DeJsonErr> {
+        if s.tok == DeJsonTok::Null {
+            s.next_tok(i)?;
+            return Ok(Self::Path);
+        }
+        s.string(i)?;
+        Ok(Self::Other)
+    }
+}
+
+#[derive(Debug, DeJson, Clone, PartialEq, Eq, Hash)]
+#[nserde(transparent)]
+pub struct PackageId(String);
+
+/// The `resolve` of the metadata, which is `null` with `--no-deps`.
+#[derive(Debug, Default)]
+struct Resolve {
+    nodes: Vec<Node>,
+}
+
 #[derive(Debug, DeJson)]
-pub struct Dependency {
-    name: PackageName,
-    kind: DependencyKind,
+struct ResolveNodes {
+    nodes: Vec<Node>,
 }
 
-impl Dependency {
-    pub fn name(&self) -> &PackageName {
-        &self.name
+impl DeJson for Resolve {
+    fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Self, DeJsonErr> {
+        if s.tok == DeJsonTok::Null {
+            s.next_tok(i)?;
+            return Ok(Self::default());
+        }
+
+        let ResolveNodes { nodes } = ResolveNodes::de_json(s, i)?;
+        Ok(Self { nodes })
     }
+}
+
+/// A package in the resolved dependency graph.
+#[derive(Debug, DeJson)]
+pub struct Node {
+    id: PackageId,
+    deps: Vec<NodeDep>,
+    features: Vec<String>,
+}
 
-    pub fn kind(&self) -> DependencyKind {
-        self.kind
+impl Node {
+    pub fn id(&self) -> &PackageId {
+        &self.id
     }
+
+    pub fn deps(&self) -> &[NodeDep] {
+        &self.deps
+    }
+
+    /// The enabled features of the package.
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+}
+
+#[derive(Debug, DeJson)]
+pub struct NodeDep {
+    pkg: PackageId,
+    dep_kinds: Vec<DepKindInfo>,
+}
+
+impl NodeDep {
+    pub fn pkg(&self) -> &PackageId {
+        &self.pkg
+    }
+
+    /// The kinds of the dependency, e.g. both "normal" and "build".
+    pub fn kinds(&self) -> impl Iterator<Item = DependencyKind> {
+        self.dep_kinds.iter().map(|k| k.kind)
+    }
+}
+
+#[derive(Debug, DeJson)]
+struct DepKindInfo {
+    kind: DependencyKind,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DependencyKind {
     Normal,
     Build,
+    Dev,
     Other,
 }
 
@@ -112,6 +228,7 @@ impl DeJson for DependencyKind {
         s.string(i)?;
         match s.strbuf.as_ref() {
             "build" => Ok(Self::Build),
+            "dev" => Ok(Self::Dev),
             _ => Ok(Self::Other),
         }
     }
@@ -158,6 +275,7 @@ impl TargetName {
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TargetKind {
     Bin,
+    Example,
     Lib,
     RLib,
     CustomBuild,
@@ -170,6 +288,7 @@ impl DeJson for TargetKind {
         s.string(i)?;
         match s.strbuf.as_ref() {
             "bin" => Ok(Self::Bin),
+            "example" => Ok(Self::Example),
             "lib" => Ok(Self::Lib),
             "rlib" => Ok(Self::RLib),
             "custom-build" => Ok(Self::CustomBuild),
@@ -179,10 +298,10 @@ impl DeJson for TargetKind {
     }
 }
 
-fn run_cargo(cargo: &Cargo) -> Result<String> {
+fn run_cargo<'a>(cargo: &Cargo, args: impl IntoIterator<Item = &'a str>) -> Result<String> {
     let mut cmd = cargo.cmd("metadata", StdioMode::LoudCapture);
 
-    cmd.args(["--format-version=1", "--no-deps"]);
+    cmd.arg("--format-version=1").args(args);
 
     log::trace!("Running {cmd:?}");
 
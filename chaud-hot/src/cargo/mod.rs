@@ -2,6 +2,11 @@ pub use self::builder::*;
 pub use self::run::*;
 
 mod builder;
+mod hash;
+mod linker;
+mod manifest;
 mod run;
 
+pub mod build_script;
+pub mod dep_info;
 pub mod metadata;
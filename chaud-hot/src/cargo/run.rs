@@ -18,8 +18,10 @@ pub enum StdioMode {
 }
 
 impl Cargo {
-    pub fn new(mani: ManifestPath) -> Self {
-        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    pub fn new(mani: ManifestPath, cargo: Option<OsString>) -> Self {
+        let cargo = cargo
+            .or_else(|| env::var_os("CARGO"))
+            .unwrap_or_else(|| "cargo".into());
 
         Self { cargo, mani }
     }
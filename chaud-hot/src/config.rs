@@ -0,0 +1,95 @@
+use core::time::Duration;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(350);
+
+/// The linker used to link hot-reloaded libraries (see `cargo::linker`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReloadLinker {
+    /// `mold` or `ld.lld` if available (falling back to the original linker if
+    /// linking fails), otherwise the original linker.
+    #[default]
+    Auto,
+    /// The linker the binary was linked with.
+    Original,
+    Lld,
+    Mold,
+}
+
+/// The worker configuration, usually created from `chaud::Config`.
+///
+/// Options that are not set are taken from `[package.metadata.chaud]` in the
+/// root package's manifest (see `cargo::metadata::ManifestConfig`), or
+/// defaulted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub debounce: Option<Duration>,
+    /// Poll for file changes with this interval, instead of using the native
+    /// watcher.
+    pub poll: Option<Duration>,
+    /// Additional paths to watch (recursively), relative to the workspace
+    /// root.
+    pub watch: Vec<PathBuf>,
+    /// Glob patterns of paths whose changes are ignored.
+    pub ignore: Vec<String>,
+    /// Glob patterns of (non-Rust) files whose changes start a build.
+    pub assets: Vec<String>,
+    /// Names of packages to watch and reload, in addition to the path
+    /// packages.
+    pub packages: Vec<String>,
+    /// Additional arguments for `cargo rustc`.
+    pub cargo_args: Vec<String>,
+    pub cargo: Option<OsString>,
+    pub profile: Option<String>,
+    pub target: Option<String>,
+    /// Whether to only reload when requested (see `workspace::command`),
+    /// instead of on every file change.
+    pub manual: Option<bool>,
+    /// The name of a signal that requests a reload, e.g. `SIGUSR1`.
+    pub reload_signal: Option<String>,
+    pub reload_linker: Option<ReloadLinker>,
+    /// Whether to install `minilog`. This cannot be set in the
+    /// manifest, since it must be decided before the manifest is loaded.
+    pub minilog: Option<bool>,
+}
+
+impl Config {
+    /// Use the options of `self`, falling back to `other` for unset options.
+    /// Lists are concatenated.
+    #[must_use]
+    pub(crate) fn or(self, other: Self) -> Self {
+        Self {
+            debounce: self.debounce.or(other.debounce),
+            poll: self.poll.or(other.poll),
+            watch: [other.watch, self.watch].concat(),
+            ignore: [other.ignore, self.ignore].concat(),
+            assets: [other.assets, self.assets].concat(),
+            packages: [other.packages, self.packages].concat(),
+            cargo_args: [other.cargo_args, self.cargo_args].concat(),
+            cargo: self.cargo.or(other.cargo),
+            profile: self.profile.or(other.profile),
+            target: self.target.or(other.target),
+            manual: self.manual.or(other.manual),
+            reload_signal: self.reload_signal.or(other.reload_signal),
+            reload_linker: self.reload_linker.or(other.reload_linker),
+            minilog: self.minilog.or(other.minilog),
+        }
+    }
+
+    pub(crate) fn debounce(&self) -> Duration {
+        self.debounce.unwrap_or(DEFAULT_DEBOUNCE)
+    }
+
+    pub(crate) fn manual(&self) -> bool {
+        self.manual.unwrap_or(false)
+    }
+
+    pub(crate) fn reload_linker(&self) -> ReloadLinker {
+        self.reload_linker.unwrap_or_default()
+    }
+
+    pub(crate) fn minilog(&self) -> bool {
+        self.minilog.unwrap_or(true)
+    }
+}
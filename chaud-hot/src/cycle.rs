@@ -1,8 +1,11 @@
-use core::sync::atomic::{AtomicU32, Ordering::Relaxed};
+use crate::{dylib, func};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering::Relaxed};
 use parking_lot::{Condvar, Mutex};
 
 static EPOCH: AtomicU32 = AtomicU32::new(0);
 
+static DEFERRED: AtomicBool = AtomicBool::new(false);
+
 static WAIT: (Mutex<()>, Condvar) = (Mutex::new(()), Condvar::new());
 
 #[inline]
@@ -26,6 +29,35 @@ pub fn wait(epoch: &mut u32) {
     WAIT.1.wait_while(&mut WAIT.0.lock(), |_| !check(epoch));
 }
 
+pub fn set_deferred(deferred: bool) {
+    DEFERRED.store(deferred, Relaxed);
+
+    if !deferred {
+        // Don't leave any updates that were deferred until now behind.
+        apply_pending();
+    }
+}
+
+#[expect(clippy::must_use_candidate, reason = "usually called for its effect")]
+pub fn apply_pending() -> bool {
+    let applied = func::begin().commit();
+    if applied == 0 {
+        return false;
+    }
+
+    log::info!("Applied {applied} deferred updates");
+    did_reload();
+    true
+}
+
+pub fn set_auto_rollback(calls: u32, whole_reload: bool) {
+    func::set_auto_rollback(calls, whole_reload);
+}
+
+pub fn set_unloading(rollback_depth: Option<u32>) {
+    dylib::set_unloading(rollback_depth);
+}
+
 /// Utility to track "init done" in Chaud integration tests.
 pub fn track_init() {
     EPOCH.store(u32::MAX, Relaxed);
@@ -37,6 +69,10 @@ pub(crate) fn init_done() {
     WAIT.1.notify_all();
 }
 
+pub(crate) fn is_deferred() -> bool {
+    DEFERRED.load(Relaxed)
+}
+
 pub(crate) fn did_reload() {
     EPOCH.fetch_add(1, Relaxed);
     WAIT.1.notify_all();
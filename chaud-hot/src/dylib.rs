@@ -1,9 +1,13 @@
+use crate::func::{self, Txn};
 use crate::util::etx;
+use crate::workspace::clean;
 use anyhow::{Context as _, Result};
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use core::ffi::c_int;
 use core::mem;
 use libloading::os::unix as ll;
+use parking_lot::Mutex;
+use std::fs;
 
 // "deep bind" ensure that a dylib being loaded will prefer its own symbols
 // over symbols available at the global scope.
@@ -13,15 +17,120 @@ const DEEP_BIND: c_int = libc::RTLD_DEEPBIND;
 #[cfg(not(target_os = "linux"))]
 const DEEP_BIND: c_int = 0;
 
-pub fn load(path: &Utf8Path) -> Result<()> {
+/// The rollback depth to preserve, if unloading is enabled.
+static UNLOADING: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Libraries loaded while unloading was enabled, which have not been unloaded
+/// yet.
+static LOADED: Mutex<Vec<Loaded>> = Mutex::new(Vec::new());
+
+struct Loaded {
+    id: u32,
+    lib: ll::Library,
+    path: Utf8PathBuf,
+    size: u64,
+    /// The first generation that may contain values from this library.
+    live_from: u32,
+    /// The first generation in which this library was found to be unreferenced.
+    dead_from: Option<u32>,
+}
+
+pub fn set_unloading(rollback_depth: Option<u32>) {
+    *UNLOADING.lock() = rollback_depth;
+    func::track_in_flight(rollback_depth.is_some());
+}
+
+/// Whether unloading is enabled (see [`set_unloading`]).
+pub fn is_unloading() -> bool {
+    UNLOADING.lock().is_some()
+}
+
+/// Load the library at `path`, attributing its updates to `id` (which must not
+/// be `0`).
+pub fn load(txn: &mut Txn, id: u32, path: &Utf8Path) -> Result<()> {
+    let live_from = txn.generation().saturating_add(1);
+
     // SAFETY: We cannot guarantee anything about the initialization routines.
-    // This is covered under the `unsafe-hot-reload` feature opt-in. We'll never
-    // run termination routines.
-    let lib = unsafe { ll::Library::open(Some(path), ll::RTLD_GLOBAL | ll::RTLD_NOW | DEEP_BIND) };
+    // This is covered under the `unsafe-hot-reload` feature opt-in. We'll only
+    // run termination routines if the user opted into unloading.
+    let lib = txn.load(id, || unsafe {
+        ll::Library::open(Some(path), ll::RTLD_GLOBAL | ll::RTLD_NOW | DEEP_BIND)
+    });
 
     let lib = lib.with_context(etx!("Failed to load {path:?}"))?;
 
-    mem::forget(lib);
+    if UNLOADING.lock().is_none() {
+        mem::forget(lib);
+        return Ok(());
+    }
+
+    let size = match fs::metadata(path) {
+        Ok(m) => m.len(),
+        Err(e) => {
+            log::debug!("Failed to get the size of {path:?}: {e}");
+            0
+        }
+    };
+
+    LOADED.lock().push(Loaded {
+        id,
+        lib,
+        path: path.to_owned(),
+        size,
+        live_from,
+        dead_from: None,
+    });
 
     Ok(())
 }
+
+/// Unload all libraries that can no longer be reached: They are not referenced
+/// by the current value or the (pruned) history of any function, and no call
+/// that may have entered them is still in flight.
+pub fn collect() {
+    let Some(rollback_depth) = *UNLOADING.lock() else {
+        return;
+    };
+
+    let mut txn = func::begin();
+    let mut loaded = LOADED.lock();
+    if loaded.is_empty() {
+        return;
+    }
+
+    txn.prune(rollback_depth);
+    let referenced = txn.libs();
+    let generation = txn.generation();
+    drop(txn);
+
+    let mut dead = Vec::new();
+    for mut l in mem::take(&mut *loaded) {
+        let reachable = referenced.contains(&l.id) || {
+            let dead_from = *l.dead_from.get_or_insert(generation);
+            !func::idle(l.live_from, dead_from)
+        };
+
+        match reachable {
+            true => loaded.push(l),
+            false => dead.push(l),
+        }
+    }
+    drop(loaded);
+
+    for l in dead {
+        unload(l);
+    }
+}
+
+fn unload(Loaded { lib, path, size, .. }: Loaded) {
+    log::trace!("Unloading {path:?}");
+
+    if let Err(e) = lib.close() {
+        log::warn!("Failed to unload {path:?}: {e}");
+        return;
+    }
+
+    log::info!("Unloaded {path:?}, reclaiming {} KiB", size / 1024);
+
+    clean::remove(&path);
+}
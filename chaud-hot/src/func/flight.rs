@@ -0,0 +1,74 @@
+//! Counting of in-flight calls, so that libraries are only unloaded once no
+//! call can still be executing their code.
+//!
+//! Calls are counted by the generation in which they started. A call that
+//! started in generation `g` can only have entered libraries that contained a
+//! live function in generation `g` (or the values of a concurrent commit, see
+//! [`super::VersionedFnPtr`]).
+
+use super::txn;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+use core::sync::atomic::{AtomicBool, AtomicU32, fence};
+
+/// The number of counters. Generations that are further apart than this share
+/// a counter, which only makes [`idle`] more conservative.
+const RING: u32 = 64;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static IN_FLIGHT: [AtomicU32; RING as usize] = [const { AtomicU32::new(0) }; RING as usize];
+
+pub fn track_in_flight(enabled: bool) {
+    ENABLED.store(enabled, Relaxed);
+}
+
+pub(super) struct Flight {
+    generation: u32,
+}
+
+#[inline]
+pub(super) fn enter() -> Option<Flight> {
+    if !ENABLED.load(Relaxed) {
+        return None;
+    }
+
+    loop {
+        let generation = txn::current();
+        counter(generation).fetch_add(1, SeqCst);
+
+        // Pairs with the fence in `idle`: Either the generation did not change
+        // (and `idle` observes the increment), or we try again.
+        if txn::current_seq_cst() == generation {
+            return Some(Flight { generation });
+        }
+
+        counter(generation).fetch_sub(1, Release);
+    }
+}
+
+impl Drop for Flight {
+    #[inline]
+    fn drop(&mut self) {
+        counter(self.generation).fetch_sub(1, Release);
+    }
+}
+
+/// Returns `true` if no call that may have entered a library that was live from
+/// generation `from` (inclusive) to `to` (exclusive) is still in flight.
+pub fn idle(from: u32, to: u32) -> bool {
+    fence(SeqCst);
+
+    // Calls that race with a commit may observe its values, so they may have
+    // entered the library if they started one generation early.
+    let from = from.saturating_sub(1);
+
+    match to.saturating_sub(from) >= RING {
+        true => IN_FLIGHT.iter().all(|c| c.load(Acquire) == 0),
+        false => (from..to).all(|g| counter(g).load(Acquire) == 0),
+    }
+}
+
+#[expect(clippy::indexing_slicing, reason = "the index is always in bounds")]
+fn counter(generation: u32) -> &'static AtomicU32 {
+    &IN_FLIGHT[(generation % RING) as usize]
+}
@@ -0,0 +1,123 @@
+//! Automatic rollback of functions that panic shortly after being reloaded.
+//!
+//! While enabled, every call of a function updated by the latest commit is
+//! wrapped in a [`Guard`], until the configured number of calls since that
+//! update has been reached. If the call unwinds, the function (or the entire
+//! latest reload) is rolled back.
+//!
+//! If a transaction is active while unwinding (e.g. the function was called by
+//! a constructor of a library that is being loaded), the rollback is deferred
+//! until the worker calls [`apply_deferred`].
+
+use super::{VersionedFnPtr, txn};
+use crate::cycle;
+use core::mem;
+use core::sync::atomic::Ordering::Relaxed;
+use core::sync::atomic::{AtomicBool, AtomicU32};
+use parking_lot::Mutex;
+use std::thread;
+
+/// The number of calls after an update during which a panic causes a rollback.
+/// `0` if disabled.
+static LIMIT: AtomicU32 = AtomicU32::new(0);
+
+/// Whether to roll back the entire latest reload instead of just the function
+/// that panicked.
+static WHOLE: AtomicBool = AtomicBool::new(false);
+
+/// Rollbacks that could not be performed while unwinding.
+static DEFERRED: Mutex<Vec<Rollback>> = Mutex::new(Vec::new());
+
+pub fn set_auto_rollback(calls: u32, whole_reload: bool) {
+    WHOLE.store(whole_reload, Relaxed);
+    LIMIT.store(calls, Relaxed);
+}
+
+pub(super) struct Guard {
+    func: &'static VersionedFnPtr,
+    limit: u32,
+    /// The generation at the start of the call.
+    generation: u32,
+    /// Whether the thread was already panicking at the start of the call (i.e.,
+    /// the call happens while unwinding from a different panic).
+    was_panicking: bool,
+}
+
+#[inline]
+pub(super) fn enter(func: &'static VersionedFnPtr) -> Option<Guard> {
+    let limit = LIMIT.load(Relaxed);
+    if limit == 0 || !func.is_fresh() || !func.count_call(limit) {
+        return None;
+    }
+
+    Some(Guard {
+        func,
+        limit,
+        generation: txn::current(),
+        was_panicking: thread::panicking(),
+    })
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if self.was_panicking || !thread::panicking() {
+            return;
+        }
+
+        let Self { func, limit, generation, .. } = *self;
+        let name = func.name();
+        let epoch = cycle::current();
+        let rollback = Rollback { func, generation, whole: WHOLE.load(Relaxed) };
+
+        log::warn!(
+            "{name:?} panicked within {limit} calls after being reloaded (epoch {epoch}), rolling back {}",
+            match rollback.whole {
+                true => "the latest reload",
+                false => "the function",
+            }
+        );
+
+        // Locking here could deadlock if this thread already holds the lock.
+        let Some(txn) = txn::try_begin() else {
+            log::info!("A reload is in progress, deferring the rollback");
+            DEFERRED.lock().push(rollback);
+            return;
+        };
+        rollback.apply(txn);
+    }
+}
+
+/// Perform the rollbacks that were deferred because a transaction was active.
+pub fn apply_deferred() {
+    let deferred = mem::take(&mut *DEFERRED.lock());
+    for rollback in deferred {
+        rollback.apply(txn::begin());
+    }
+}
+
+struct Rollback {
+    func: &'static VersionedFnPtr,
+    /// The generation at the start of the call that panicked.
+    generation: u32,
+    whole: bool,
+}
+
+impl Rollback {
+    fn apply(self, txn: txn::Txn) {
+        if txn::current() != self.generation {
+            // Something was reloaded or rolled back in the meantime (possibly
+            // due to a concurrent panic).
+            log::debug!("Not rolling back {:?}, it was updated", self.func.name());
+            return;
+        }
+
+        let reverted = match self.whole {
+            true => txn.rollback().is_some_and(|count| count > 0),
+            false => txn.revert(self.func),
+        };
+
+        if reverted {
+            cycle::did_reload();
+        }
+    }
+}
@@ -21,9 +21,11 @@
 //!   pointers).
 //! * [`AtomicFnPtr`] conceptually wraps an [`ErasedFnPtr`], allowing it to be
 //!   atomically updated.
+//! * [`VersionedFnPtr`] combines two [`AtomicFnPtr`]s, allowing them to be
+//!   updated as part of a [transaction][txn].
 //! * [`FuncStorage`] is parmeterized by [`Func`] and stores the corresponding
-//!   [`AtomicFnPtr`]. It is the boundary between erased and non-erased (typed)
-//!   layers.
+//!   [`VersionedFnPtr`]. It is the boundary between erased and non-erased
+//!   (typed) layers.
 //!
 //! # Safety
 //!
@@ -35,8 +37,8 @@
 //!    be a function pointer implementing [`FnPtrLike`].
 //!    * This implies that stored values are always non-null.
 //!
-//! 2) With the exception of [`AtomicFnPtr`], the stored **value** must never
-//!    change.
+//! 2) With the exception of [`AtomicFnPtr`] and [`VersionedFnPtr`], the stored
+//!    **value** must never change.
 //!    * This makes it easier to reason about (1).
 //!
 //! # Code Style
@@ -49,10 +51,18 @@
 
 pub use self::atomic::*;
 pub use self::def::*;
+pub use self::flight::{idle, track_in_flight};
+pub use self::guard::{apply_deferred, set_auto_rollback};
 pub use self::ptr::*;
 pub use self::storage::*;
+pub use self::txn::{Txn, begin, rollbacks};
+pub use self::versioned::*;
 
 mod atomic;
 mod def;
+mod flight;
+mod guard;
 mod ptr;
 mod storage;
+mod txn;
+mod versioned;
@@ -1,8 +1,7 @@
-use super::{AtomicFnPtr, ErasedFnPtr, Func};
+use super::{ErasedFnPtr, Func, VersionedFnPtr, flight, guard, txn};
 use core::marker::PhantomData;
 
 /// Stores the necessary runtime information about a hot-reloadable function.
-#[repr(transparent)]
 pub struct FuncStorage<F: Func> {
     _pd: PhantomData<F>,
     /// # Safety
@@ -11,14 +10,14 @@ pub struct FuncStorage<F: Func> {
     ///
     /// * The actual type must never change.
     /// * The contained actual type must be `F::Ptr`.
-    inner: AtomicFnPtr,
+    inner: VersionedFnPtr,
 }
 
 impl<F: Func> FuncStorage<F> {
     #[must_use]
     #[expect(clippy::new_without_default, reason = "default would be unused")]
     pub const fn new() -> Self {
-        let inner = AtomicFnPtr::new(ErasedFnPtr::erase::<F>(F::actual));
+        let inner = VersionedFnPtr::new(F::NAME, ErasedFnPtr::erase::<F>(F::actual));
 
         // SAFETY: Initializing does not count as a change, and the actual type
         // requirements are enforced or need to be upheld by the caller.
@@ -28,18 +27,31 @@ impl<F: Func> FuncStorage<F> {
     #[inline]
     #[must_use]
     pub fn get(&'static self) -> F::Ptr {
-        let erased = self.inner.load_relaxed();
+        let erased = self.inner.load();
 
         // SAFETY: `inner`'s actual type is `F::Ptr`.
         unsafe { erased.typed::<F::Ptr>() }
     }
 
+    /// Call `f` with the current version of this function.
+    ///
+    /// This allows rolling back the function if it panics shortly after being
+    /// reloaded, and tracks the call as in flight while unloading is enabled.
+    #[inline]
+    pub fn call<R>(&'static self, f: impl FnOnce(F::Ptr) -> R) -> R {
+        let _flight = flight::enter();
+        let _guard = guard::enter(&self.inner);
+        f(self.get())
+    }
+
+    /// Stage an update to the latest version of this function, to be applied
+    /// by the next commit of a transaction.
     pub fn update(&'static self) {
         let erased = ErasedFnPtr::erase::<F>(F::actual);
 
         // SAFETY: `inner`'s actual type is `F::Ptr`.
-        unsafe { self.inner.store_relaxed(erased) };
+        unsafe { self.inner.stage(erased, txn::loading()) };
 
-        log::debug!("Updated {:?} to {:?}", F::NAME, erased);
+        log::debug!("Staged {:?} as {:?}", F::NAME, erased);
     }
 }
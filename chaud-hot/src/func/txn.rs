@@ -0,0 +1,266 @@
+//! Transactional updates of hot-reloadable functions.
+//!
+//! All updates from a single library are first *staged*, which does not
+//! affect any running code. Afterwards, all staged updates are *committed* at
+//! once, by incrementing the global generation with `Release` ordering.
+//!
+//! [`VersionedFnPtr`] loads the generation with `Acquire` ordering before
+//! loading a function pointer. Thus any call that starts after a commit
+//! observes every update from that commit.
+//!
+//! Every [`VersionedFnPtr`] remembers the generation of each of its updates,
+//! which allows a [rollback][Txn::rollback] to the state of a previous
+//! generation. It also remembers the library each update originates from,
+//! which determines whether a library is still [referenced][Txn::libs].
+
+use super::VersionedFnPtr;
+use core::mem;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+use hashbrown::HashSet;
+use parking_lot::{Mutex, MutexGuard};
+
+/// The largest generation that can be committed (see
+/// [`VersionedFnPtr`], which packs it with another bit).
+pub(super) const MAX_GENERATION: u32 = u32::MAX >> 1;
+
+/// The latest committed generation.
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// The number of rollbacks (including reverts of single functions) so far.
+static ROLLBACKS: AtomicU32 = AtomicU32::new(0);
+
+/// Held while loading a library, and while committing.
+static LOCK: Mutex<Lineage> = Mutex::new(Lineage { generations: Vec::new(), pruned: false });
+
+/// The library currently being loaded, see [`Txn::load`]. `0` refers to the
+/// executable.
+static LOADING: AtomicU32 = AtomicU32::new(0);
+
+/// All functions with staged (but uncommitted) updates.
+static STAGED: Mutex<Vec<&'static VersionedFnPtr>> = Mutex::new(Vec::new());
+
+/// Every function that has ever been staged.
+static FUNCS: Mutex<Vec<&'static VersionedFnPtr>> = Mutex::new(Vec::new());
+
+/// The committed generations that a rollback can return to.
+///
+/// Generation `0` (the initial state) is implicitly the first element, unless
+/// the lineage has been pruned.
+struct Lineage {
+    generations: Vec<u32>,
+    pruned: bool,
+}
+
+/// An exclusive transaction.
+///
+/// Updates staged while the transaction is active (i.e., by the constructors
+/// of a library loaded in the meantime) are only published once
+/// [`Txn::commit`] is called. If the transaction is dropped without being
+/// committed, they stay staged until the next commit.
+pub struct Txn {
+    lineage: MutexGuard<'static, Lineage>,
+}
+
+pub fn begin() -> Txn {
+    Txn { lineage: LOCK.lock() }
+}
+
+/// Like [`begin`], but returns `None` instead of blocking if a transaction is
+/// active (possibly on the current thread).
+pub(super) fn try_begin() -> Option<Txn> {
+    LOCK.try_lock().map(|lineage| Txn { lineage })
+}
+
+impl Txn {
+    /// Publish all staged updates.
+    ///
+    /// Returns the number of updates published.
+    pub fn commit(mut self) -> usize {
+        commit(&mut self.lineage)
+    }
+
+    /// The latest committed generation.
+    #[must_use]
+    #[expect(clippy::unused_self, reason = "requires an active transaction")]
+    pub fn generation(&self) -> u32 {
+        current()
+    }
+
+    /// Call `f`, attributing any updates staged in the meantime to the library
+    /// identified by `lib`.
+    #[expect(clippy::unused_self, reason = "requires an active transaction")]
+    pub fn load<T>(&mut self, lib: u32, f: impl FnOnce() -> T) -> T {
+        LOADING.store(lib, Relaxed);
+        let res = f();
+        LOADING.store(0, Relaxed);
+        res
+    }
+
+    /// Forget about all states that are more than `depth` rollbacks in the
+    /// past.
+    pub fn prune(&mut self, depth: u32) {
+        let lineage = &mut *self.lineage;
+        let keep = usize::try_from(depth)
+            .unwrap_or(usize::MAX)
+            .saturating_add(1);
+        let Some(excess) = lineage.generations.len().checked_sub(keep) else {
+            return;
+        };
+        lineage.generations.drain(..excess);
+        lineage.pruned = true;
+
+        // The oldest state we can still roll back to.
+        let Some(&floor) = lineage.generations.first() else {
+            return;
+        };
+
+        for func in FUNCS.lock().iter() {
+            func.prune(floor);
+        }
+    }
+
+    /// The libraries that are referenced by any function, either by its
+    /// current value or by its history.
+    #[must_use]
+    #[expect(clippy::unused_self, reason = "requires an active transaction")]
+    pub fn libs(&self) -> HashSet<u32> {
+        let mut libs = HashSet::new();
+        for func in FUNCS.lock().iter() {
+            func.libs(&mut libs);
+        }
+        libs
+    }
+
+    /// Revert every function to its state before the latest commit. Any
+    /// staged updates are discarded.
+    ///
+    /// Repeated rollbacks go back further in time.
+    ///
+    /// Returns the number of reverted functions, or `None` if there is no
+    /// earlier state to return to.
+    pub fn rollback(mut self) -> Option<usize> {
+        let pruned = self.lineage.pruned;
+        let generations = &mut self.lineage.generations;
+        if pruned && generations.len() < 2 {
+            // The earlier states have been pruned.
+            return None;
+        }
+        generations.pop()?;
+        let target = generations.pop().unwrap_or(0);
+
+        // Staged functions are reverted as well (see below), so we only need to
+        // forget about them here.
+        let discarded = mem::take(&mut *STAGED.lock()).len();
+        if discarded > 0 {
+            log::info!("Rollback discards {discarded} staged updates");
+        }
+
+        log::debug!("Rolling back to generation {target}");
+
+        let funcs = FUNCS.lock().clone();
+        for func in funcs {
+            func.revert(target);
+        }
+
+        let count = commit(&mut self.lineage);
+        if count > 0 {
+            ROLLBACKS.fetch_add(1, Relaxed);
+        }
+        if count == 0 && target != 0 {
+            // Nothing was committed, so the state of `target` is still the
+            // current one.
+            self.lineage.generations.push(target);
+        }
+
+        Some(count)
+    }
+
+    /// Revert only `func` to its value before its latest committed update.
+    ///
+    /// Returns `false` if `func` has never been updated, or if updates of
+    /// other functions are staged (which this would publish as well).
+    pub(super) fn revert(mut self, func: &'static VersionedFnPtr) -> bool {
+        let pending = STAGED.lock().len();
+        if pending > 0 {
+            log::warn!(
+                "Not reverting {:?}, {pending} other updates are staged",
+                func.name()
+            );
+            return false;
+        }
+
+        func.revert_latest();
+
+        let reverted = commit(&mut self.lineage) > 0;
+        if reverted {
+            ROLLBACKS.fetch_add(1, Relaxed);
+        }
+        reverted
+    }
+}
+
+fn commit(lineage: &mut Lineage) -> usize {
+    let mut staged = STAGED.lock();
+    if staged.is_empty() {
+        return 0;
+    }
+
+    // The overflow is checked while staging.
+    let generation = GENERATION.fetch_add(1, Release) + 1;
+    lineage.generations.push(generation);
+
+    let count = staged.len();
+    for func in staged.drain(..) {
+        func.published(generation);
+        log::trace!("Published {:?}", func.name());
+    }
+    log::debug!("Published {count} updates (generation {generation})");
+
+    count
+}
+
+/// The number of rollbacks so far, to detect whether anything was rolled back
+/// in the meantime.
+pub fn rollbacks() -> u32 {
+    ROLLBACKS.load(Relaxed)
+}
+
+/// The latest committed generation.
+#[inline]
+pub(super) fn current() -> u32 {
+    GENERATION.load(Acquire)
+}
+
+/// Like [`current`], but with `SeqCst` ordering.
+#[inline]
+pub(super) fn current_seq_cst() -> u32 {
+    GENERATION.load(SeqCst)
+}
+
+/// The library currently being loaded.
+pub(super) fn loading() -> u32 {
+    LOADING.load(Relaxed)
+}
+
+/// Stage an update of `func`.
+///
+/// `f` is called with the generation the update will be published in.
+pub(super) fn stage(func: &'static VersionedFnPtr, f: impl FnOnce(u32)) {
+    let mut staged = STAGED.lock();
+
+    // Commits hold `STAGED`, so the generation cannot change concurrently.
+    let next = GENERATION.load(Relaxed).checked_add(1);
+    let Some(next) = next.filter(|&n| n <= MAX_GENERATION) else {
+        log::error!("Generation overflowed, not updating {:?}", func.name());
+        return;
+    };
+
+    f(next);
+    staged.push(func);
+}
+
+/// Remember `func`, so that it is considered by future rollbacks.
+pub(super) fn register(func: &'static VersionedFnPtr) {
+    FUNCS.lock().push(func);
+}
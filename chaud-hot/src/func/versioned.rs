@@ -0,0 +1,357 @@
+use super::{AtomicFnPtr, ErasedFnPtr, txn};
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use hashbrown::HashSet;
+use parking_lot::Mutex;
+
+/// An [`AtomicFnPtr`] that is updated [transactionally][txn].
+///
+/// Two slots are used, so that calls can keep using the committed value from
+/// one slot while a new value is staged in the other slot. A staged value is
+/// never written to the slot that holds the committed value.
+///
+/// A call that races with two consecutive commits may observe the value from
+/// the later commit. Since commits are usually separated by (at least) a Cargo
+/// build, this is not a concern in practice.
+pub struct VersionedFnPtr {
+    /// The name of the function.
+    ///
+    /// This must not be taken from a reloaded library, which may be unloaded.
+    name: &'static str,
+    /// The generation in which the latest value was staged, and the slot that
+    /// contains it, packed by [`pack`]. If the generation is not committed
+    /// yet, the other slot contains the committed value.
+    latest: AtomicU32,
+    /// # Safety
+    ///
+    /// See the [module][super#safety] docs:
+    ///
+    /// * The actual type must never change.
+    /// * The actual type must be the same for both slots.
+    /// * The actual type must be a function pointer implementing
+    ///   [`super::FnPtrLike`].
+    slots: [AtomicFnPtr; 2],
+    /// Every value ever staged, in order (minus those that have been
+    /// [pruned][Self::prune]). Empty if no value has been staged yet.
+    ///
+    /// # Safety
+    ///
+    /// * The actual type of every value must be the same as that of `slots`.
+    history: Mutex<Vec<Version>>,
+    /// The number of calls since the latest update was published, counted
+    /// only up to the limit passed to [`Self::count_call`].
+    calls: AtomicU32,
+    /// The generation in which the latest update was published, or `0`.
+    published: AtomicU32,
+    /// The library that contains `self`, determined when the first update is
+    /// staged. `0` for the application itself.
+    ///
+    /// A function that did not exist in the application is stored in the first
+    /// library that contains it. That library is therefore never unloaded
+    /// (even once the function was updated again), as [`txn`] keeps referring
+    /// to `self`.
+    home: AtomicU32,
+}
+
+#[derive(Copy, Clone)]
+struct Version {
+    generation: u32,
+    val: ErasedFnPtr,
+    /// The library `val` points into (see [`txn::Txn::load`]).
+    lib: u32,
+}
+
+impl VersionedFnPtr {
+    #[inline]
+    #[must_use]
+    pub(super) const fn new(name: &'static str, f: ErasedFnPtr) -> Self {
+        // SAFETY: Initializing defines the actual type stored, which is the
+        // same for both slots. The other requirements are enforced by
+        // `ErasedFnPtr`.
+        Self {
+            name,
+            latest: AtomicU32::new(pack(0, 0)),
+            slots: [AtomicFnPtr::new(f), AtomicFnPtr::new(f)],
+            history: Mutex::new(Vec::new()),
+            calls: AtomicU32::new(0),
+            published: AtomicU32::new(0),
+            home: AtomicU32::new(0),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub(super) fn load(&self) -> ErasedFnPtr {
+        let current = txn::current();
+        let (changed, slot) = unpack(self.latest.load(Acquire));
+
+        let slot = match changed <= current {
+            true => slot,
+            // The latest value is only staged, use the committed one.
+            false => slot ^ 1,
+        };
+
+        self.slot(slot).load_relaxed()
+    }
+
+    /// Stage an update to `f`, which points into the library `lib`. See
+    /// [`txn`].
+    ///
+    /// # Safety
+    ///
+    /// The passed argument must have the same actual type as `self`.
+    pub(super) unsafe fn stage(&'static self, f: ErasedFnPtr, lib: u32) {
+        txn::stage(self, |next| {
+            let (changed, slot) = unpack(self.latest.load(Relaxed));
+
+            // If `changed` is committed, calls use its slot (both before and
+            // after we update `latest`), so stage into the other one.
+            // Otherwise, we replace a value staged for the same generation.
+            let staged = match changed == next {
+                true => slot,
+                false => slot ^ 1,
+            };
+
+            // SAFETY: The caller must ensure that `f` has the same actual type
+            // as `self`.
+            unsafe { self.slot(staged).store_relaxed(f) };
+
+            if changed != next {
+                self.latest.store(pack(next, staged), Release);
+            }
+
+            let mut history = self.history.lock();
+            if history.is_empty() {
+                // This is the first update, so `slots[0]` still contains the
+                // initial value (it was staged into `slots[1]`). If that is
+                // `f`, `self` was initialized by the library being loaded.
+                let initial = self.slot(0).load_relaxed();
+                let home = match initial == f {
+                    true => lib,
+                    false => 0,
+                };
+                self.home.store(home, Relaxed);
+                history.push(Version { generation: 0, val: initial, lib: home });
+                txn::register(self);
+            }
+            // SAFETY: `f` has the same actual type as `self`.
+            history.push(Version { generation: next, val: f, lib });
+        });
+    }
+
+    #[inline]
+    #[must_use]
+    pub(super) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Stage the value that was current in `generation`, unless it is the
+    /// current value already (and nothing else is staged).
+    pub(super) fn revert(&'static self, generation: u32) {
+        let history = self.history.lock();
+        let Some(version) = history.iter().rfind(|v| v.generation <= generation) else {
+            return;
+        };
+        let Version { val, lib, .. } = *version;
+        drop(history);
+
+        let (changed, _) = unpack(self.latest.load(Relaxed));
+        let is_staged = changed > txn::current();
+        if !is_staged && self.load() == val {
+            return;
+        }
+
+        log::debug!("Reverting {:?} to {val:?}", self.name);
+
+        // SAFETY: `val` is from the history of `self`, so it has the same
+        // actual type.
+        unsafe { self.stage(val, lib) };
+    }
+
+    /// Stage the value that was current before the latest committed update.
+    pub(super) fn revert_latest(&'static self) {
+        let (changed, _) = unpack(self.latest.load(Relaxed));
+        if changed == 0 {
+            return;
+        }
+
+        self.revert(changed - 1);
+    }
+
+    /// Forget all values that were superseded before `floor` (which are not
+    /// needed to roll back to `floor` or later).
+    pub(super) fn prune(&self, floor: u32) {
+        let mut history = self.history.lock();
+
+        let start = history.iter().rposition(|v| v.generation <= floor);
+        history.drain(..start.unwrap_or(0));
+
+        // A rollback may have superseded values within the same generation.
+        let mut next_generation = None;
+        let mut keep = Vec::with_capacity(history.len());
+        for version in history.drain(..).rev() {
+            if next_generation != Some(version.generation) {
+                next_generation = Some(version.generation);
+                keep.push(version);
+            }
+        }
+        keep.reverse();
+        *history = keep;
+    }
+
+    /// Insert every library referenced by the history, and the library that
+    /// contains `self`, into `libs`.
+    pub(super) fn libs(&self, libs: &mut HashSet<u32>) {
+        libs.extend(self.history.lock().iter().map(|v| v.lib));
+        libs.insert(self.home.load(Relaxed));
+    }
+
+    /// Count a call, unless `limit` calls have been counted since the latest
+    /// update was published.
+    ///
+    /// Returns `true` if the call was counted.
+    #[inline]
+    pub(super) fn count_call(&self, limit: u32) -> bool {
+        // Avoid contended writes once the limit has been reached.
+        if self.calls.load(Relaxed) >= limit {
+            return false;
+        }
+
+        self.calls.fetch_add(1, Relaxed) < limit
+    }
+
+    /// Reset the call count after an update has been published in
+    /// `generation`.
+    pub(super) fn published(&self, generation: u32) {
+        self.calls.store(0, Relaxed);
+        self.published.store(generation, Relaxed);
+    }
+
+    /// Whether an update was published by the latest commit.
+    #[inline]
+    pub(super) fn is_fresh(&self) -> bool {
+        let published = self.published.load(Relaxed);
+        published != 0 && published == txn::current()
+    }
+
+    fn slot(&self, slot: u32) -> &AtomicFnPtr {
+        let [first, second] = &self.slots;
+        match slot {
+            0 => first,
+            _ => second,
+        }
+    }
+}
+
+/// Pack a generation (at most [`txn::MAX_GENERATION`]) and a slot (`0` or `1`)
+/// into a single value, so that both can be loaded atomically.
+const fn pack(generation: u32, slot: u32) -> u32 {
+    (generation << 1) | (slot & 1)
+}
+
+const fn unpack(packed: u32) -> (u32, u32) {
+    (packed >> 1, packed & 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::func::RawErasedFnPtr;
+    use pretty_assertions::assert_eq;
+
+    #[inline(never)]
+    fn one() -> u32 {
+        1
+    }
+
+    #[inline(never)]
+    fn two() -> u32 {
+        2
+    }
+
+    #[inline(never)]
+    fn three() -> u32 {
+        3
+    }
+
+    fn erase(f: fn() -> u32) -> ErasedFnPtr {
+        // SAFETY: `f` is a function pointer.
+        unsafe { ErasedFnPtr::from_raw_never_null(f as RawErasedFnPtr) }
+    }
+
+    fn leak(name: &'static str) -> &'static VersionedFnPtr {
+        Box::leak(Box::new(VersionedFnPtr::new(name, erase(one))))
+    }
+
+    #[test]
+    fn stage_after_skipped_generation() {
+        let foo = leak("foo");
+        let bar = leak("bar");
+
+        // Update `foo` in one generation, and only `bar` in the next one, so
+        // that the next update of `foo` is staged for a generation with the
+        // same parity as its latest one.
+        let txn = txn::begin();
+        // SAFETY: All values have the same actual type.
+        unsafe { foo.stage(erase(two), 1) };
+        txn.commit();
+
+        let txn = txn::begin();
+        // SAFETY: All values have the same actual type.
+        unsafe { bar.stage(erase(two), 1) };
+        txn.commit();
+
+        // The slot a call would use if it loaded `latest` right now.
+        let (_, slot) = unpack(foo.latest.load(Acquire));
+
+        let txn = txn::begin();
+        // SAFETY: All values have the same actual type.
+        unsafe { foo.stage(erase(three), 2) };
+
+        assert_eq!(foo.load(), erase(two));
+        assert_eq!(foo.slot(slot).load_relaxed(), erase(two));
+
+        // Staging again for the same generation replaces the staged value.
+        // SAFETY: All values have the same actual type.
+        unsafe { foo.stage(erase(one), 3) };
+        assert_eq!(foo.load(), erase(two));
+        assert_eq!(foo.slot(slot).load_relaxed(), erase(two));
+
+        txn.commit();
+        assert_eq!(foo.load(), erase(one));
+        assert_eq!(bar.load(), erase(two));
+    }
+
+    #[test]
+    fn libs() {
+        let old = leak("old");
+        let new = leak("new");
+
+        // `new` is initialized by the library that stages its first update.
+        let txn = txn::begin();
+        // SAFETY: All values have the same actual type.
+        unsafe { old.stage(erase(two), 4) };
+        // SAFETY: All values have the same actual type.
+        unsafe { new.stage(erase(one), 4) };
+        txn.commit();
+
+        let txn = txn::begin();
+        // SAFETY: All values have the same actual type.
+        unsafe { old.stage(erase(three), 5) };
+        // SAFETY: All values have the same actual type.
+        unsafe { new.stage(erase(three), 5) };
+        let generation = txn::current() + 1;
+        txn.commit();
+
+        old.prune(generation);
+        new.prune(generation);
+
+        let mut libs = HashSet::new();
+        old.libs(&mut libs);
+        assert_eq!(libs, HashSet::from([0, 5]));
+
+        let mut libs = HashSet::new();
+        new.libs(&mut libs);
+        assert_eq!(libs, HashSet::from([4, 5]));
+    }
+}
@@ -5,14 +5,19 @@
     reason = "this crate is an implementation detail, don't be as-strict"
 )]
 
+#[doc(no_inline)]
+pub use self::config::{Config, ReloadLinker};
 #[doc(no_inline)]
 pub use self::func::{Func, FuncStorage};
 #[doc(no_inline)]
+pub use self::workspace::command::{pause, reload_now, resume, rollback};
+#[doc(no_inline)]
 pub use self::workspace::worker::launch as init;
 #[doc(no_inline)]
 pub use ctor::declarative::ctor;
 
 mod cargo;
+mod config;
 mod dylib;
 mod func;
 mod util;
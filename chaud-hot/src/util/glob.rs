@@ -0,0 +1,236 @@
+//! Minimal glob patterns, as used for ignore patterns.
+//!
+//! * `*` matches any sequence of characters, except `/`.
+//! * `**` matches any sequence of characters, including `/`.
+//! * `?` matches any single character, except `/`.
+//! * `[abc]`, `[a-z]` match any single character in the class, except `/`.
+//!   `[!abc]` and `[^abc]` match any character not in the class.
+//! * `\` matches the following character literally.
+//!
+//! A pattern without a `/` (ignoring a trailing one) is matched against each
+//! component of a path. Other patterns are matched against the path, relative
+//! to some root. In both cases, matching a directory also matches everything
+//! inside it.
+
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: Box<[char]>,
+    anchored: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let pattern = pattern.strip_suffix('/').unwrap_or(pattern);
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        Self { pattern: pattern.chars().collect(), anchored }
+    }
+
+    /// Check whether the relative path `rel` matches.
+    pub fn matches(&self, rel: &str) -> bool {
+        let rel: Box<[char]> = rel.chars().collect();
+
+        let mut start = 0;
+        for (end, _) in rel.iter().enumerate().filter(|(_, c)| **c == '/') {
+            if self.matches_part(&rel, start, end) {
+                return true;
+            }
+            start = end + 1;
+        }
+
+        self.matches_part(&rel, start, rel.len())
+    }
+
+    /// Check the component from `start` to `end`, or everything up to `end` if
+    /// anchored.
+    fn matches_part(&self, rel: &[char], start: usize, end: usize) -> bool {
+        let start = match self.anchored {
+            true => 0,
+            false => start,
+        };
+
+        rel.get(start..end)
+            .is_some_and(|part| glob_match(&self.pattern, part))
+    }
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directories at all.
+            let no_dirs = match rest {
+                ['/', rest @ ..] => glob_match(rest, text),
+                _ => false,
+            };
+            no_dirs || match_star(rest, text, true)
+        }
+        ['*', rest @ ..] => match_star(rest, text, false),
+        ['?', rest @ ..] => match text {
+            [c, text @ ..] if *c != '/' => glob_match(rest, text),
+            _ => false,
+        },
+        ['[', class @ ..] => match text {
+            [c, text @ ..] if *c != '/' => match match_class(class, *c) {
+                Some((matched, rest)) => matched && glob_match(rest, text),
+                // Not a class, so the `[` is literal.
+                None => *c == '[' && glob_match(class, text),
+            },
+            _ => false,
+        },
+        ['\\', p, rest @ ..] => match text {
+            [c, text @ ..] if c == p => glob_match(rest, text),
+            _ => false,
+        },
+        [p, rest @ ..] => match text {
+            [c, text @ ..] if c == p => glob_match(rest, text),
+            _ => false,
+        },
+    }
+}
+
+/// Match `c` against the character class at the start of `pattern` (after its
+/// `[`), returning the result and the rest of the pattern. Returns `None` if the
+/// class is not terminated.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut rest) = match pattern {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, pattern),
+    };
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match rest {
+            // A `]` right after the `[` is part of the class.
+            [']', tail @ ..] if !first => return Some((matched != negated, tail)),
+            [lo, '-', hi, tail @ ..] if *hi != ']' => {
+                matched |= (*lo..=*hi).contains(&c);
+                rest = tail;
+            }
+            [x, tail @ ..] => {
+                matched |= *x == c;
+                rest = tail;
+            }
+            [] => return None,
+        }
+        first = false;
+    }
+}
+
+/// Match a `*` (or `**` if `cross`) followed by `rest`.
+fn match_star(rest: &[char], mut text: &[char], cross: bool) -> bool {
+    loop {
+        if glob_match(rest, text) {
+            return true;
+        }
+
+        match text {
+            [c, tail @ ..] if cross || *c != '/' => text = tail,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn check(pattern: &str, matching: &[&str], other: &[&str]) {
+        let glob = Glob::new(pattern);
+        for rel in matching {
+            assert!(glob.matches(rel), "{pattern:?} should match {rel:?}");
+        }
+        for rel in other {
+            assert!(!glob.matches(rel), "{pattern:?} should not match {rel:?}");
+        }
+    }
+
+    #[test]
+    fn star() {
+        check(
+            "*.tmp",
+            &["a.tmp", ".tmp", "dir/a.tmp", "a.tmp/inner"],
+            &["a.tmpx", "a.tm", "dir.tmpx/a"],
+        );
+        check(
+            "src/*.rs",
+            &["src/a.rs", "src/a.rs/b"],
+            &["src/a/b.rs", "x/src/a.rs"],
+        );
+    }
+
+    #[test]
+    fn double_star() {
+        check(
+            "**/gen",
+            &["gen", "a/gen", "a/b/gen/c"],
+            &["agen", "gen.rs"],
+        );
+        check(
+            "a/**/b",
+            &["a/b", "a/x/b", "a/x/y/b/c"],
+            &["a/xb", "b", "x/a/b"],
+        );
+        check("a/**", &["a/b", "a/b/c"], &["b/a"]);
+    }
+
+    #[test]
+    fn question_mark() {
+        check("?.o", &["a.o", "dir/b.o"], &["ab.o", ".o"]);
+        check("a?b/c", &["axb/c"], &["a/b/c", "ab/c"]);
+    }
+
+    #[test]
+    fn class() {
+        check(
+            "file[0-9].txt",
+            &["file1.txt", "a/file9.txt"],
+            &["filex.txt", "file10.txt"],
+        );
+        check("[ab]c", &["ac", "bc"], &["cc", "abc"]);
+        check("[!ab]c", &["cc"], &["ac", "bc", "c"]);
+        check("[^a]", &["b"], &["a"]);
+        check("[]]", &["]"], &["a"]);
+        check("a[/]b", &[], &["a/b"]);
+        // An unterminated class is literal.
+        check("a[b", &["a[b"], &["ab"]);
+    }
+
+    #[test]
+    fn escape() {
+        check("\\*", &["*"], &["a"]);
+        check("\\!a", &["!a"], &["a"]);
+        check("a\\[b]", &["a[b]"], &["ab"]);
+    }
+
+    #[test]
+    fn anchored() {
+        // Without a `/`, any component may match.
+        check(
+            "target",
+            &["target", "target/debug", "a/target"],
+            &["targets", "a/xtarget"],
+        );
+        // A leading or inner `/` anchors the pattern at the root.
+        check("/target", &["target", "target/debug"], &["a/target"]);
+        check(
+            "a/target",
+            &["a/target", "a/target/debug"],
+            &["b/a/target", "target"],
+        );
+    }
+
+    #[test]
+    fn trailing_slash() {
+        // A trailing `/` does not anchor the pattern.
+        check(
+            "target/",
+            &["target", "a/target", "a/target/debug"],
+            &["targets"],
+        );
+        check("/target/", &["target/debug"], &["a/target"]);
+    }
+}
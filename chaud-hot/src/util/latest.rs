@@ -2,6 +2,7 @@
 //! recently published value available to some other thread, with support
 //! for waiting for the next published value.
 
+use core::time::Duration;
 use parking_lot::{Condvar, Mutex};
 use std::sync::Arc;
 
@@ -28,6 +29,12 @@ pub fn make_latest<T: Copy>(initial: T) -> (LatestPublisher<T>, LatestReader<T>)
     )
 }
 
+impl<T> Clone for LatestPublisher<T> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
 impl<T> LatestPublisher<T> {
     pub fn publish(&mut self, val: T) {
         *self.inner.val.lock() = val;
@@ -36,15 +43,22 @@ impl<T> LatestPublisher<T> {
 }
 
 impl<T: Copy + PartialEq> LatestReader<T> {
-    pub fn wait(&mut self) -> T {
+    /// Wait for the next published value, returning `None` if no value was
+    /// published within `timeout`.
+    pub fn wait_for(&mut self, timeout: Duration) -> Option<T> {
         let mut guard = self.inner.val.lock();
 
         self.inner
             .cond
-            .wait_while(&mut guard, |val| *val == self.latest);
+            .wait_while_for(&mut guard, |val| *val == self.latest, timeout);
 
-        self.latest = *guard;
-        self.latest
+        match *guard == self.latest {
+            true => None,
+            false => {
+                self.latest = *guard;
+                Some(self.latest)
+            }
+        }
     }
 
     pub fn check(&mut self) -> Option<T> {
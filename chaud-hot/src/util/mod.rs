@@ -2,11 +2,16 @@ pub use self::command::*;
 #[expect(unused_imports, reason = "re-exports are less visible")]
 pub use self::etx::*;
 pub use self::into::*;
+pub use self::path::*;
 
 mod command;
 mod etx;
 mod into;
+mod path;
 
 pub mod assert;
+pub mod glob;
 pub mod latest;
 pub mod minilog;
+#[cfg(test)]
+pub mod temp;
@@ -0,0 +1,19 @@
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+
+/// Lexically resolve `.` and `..` components, e.g. in paths like
+/// `src/../../assets/shader.wgsl`.
+pub fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let mut out = Utf8PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                if !out.pop() {
+                    out.push(comp);
+                }
+            }
+            comp => out.push(comp),
+        }
+    }
+    out
+}
@@ -0,0 +1,40 @@
+//! Temporary directories for tests.
+
+use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use core::ops;
+use std::{env, fs, process};
+
+/// A directory that is removed (with its contents) when dropped.
+pub struct TempDir(Utf8PathBuf);
+
+impl TempDir {
+    /// Create an empty directory for the test `name`, unique to this process.
+    pub fn new(name: &str) -> Result<Self> {
+        let dir = env::temp_dir().join(format!("chaud-{name}-test-{}", process::id()));
+        let dir = Utf8PathBuf::try_from(dir).context("Non-UTF-8 temp dir")?;
+
+        // Left behind by a previous process with the same ID.
+        if dir.exists() {
+            fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {dir:?}"))?;
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {dir:?}"))?;
+        Ok(Self(dir))
+    }
+}
+
+impl ops::Deref for TempDir {
+    type Target = Utf8Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            log::warn!("Failed to remove {:?}: {e}", self.0);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Cleanup of the artifacts Chaud leaves behind.
+//!
+//! Libraries are named `<bin>.<pid>.<epoch>.hot`, so that a new process never
+//! overwrites a library that is still mapped by another process. Libraries of
+//! processes that are no longer running are removed on startup.
+
+use camino::Utf8Path;
+use std::{fs, io, process};
+
+/// The file name of the library for `epoch`.
+pub fn lib_name(bin: &str, epoch: u32) -> String {
+    format!("{bin}.{}.{epoch}.hot", process::id())
+}
+
+/// Remove all libraries in `chaud_dir` that belong to processes that are no
+/// longer running.
+pub fn stale_libs(chaud_dir: &Utf8Path) {
+    let entries = match chaud_dir.read_dir_utf8() {
+        Ok(e) => e,
+        Err(e) => {
+            log::warn!("Failed to read {chaud_dir:?}: {e}");
+            return;
+        }
+    };
+
+    let mut removed = 0_usize;
+    for entry in entries {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                log::warn!("Failed to read entry of {chaud_dir:?}: {e}");
+                continue;
+            }
+        };
+
+        let Some(stem) = entry.file_name().strip_suffix(".hot") else {
+            continue;
+        };
+
+        // Files without a (valid) pid were created by older versions.
+        let pid = stem.rsplit('.').nth(1).and_then(|p| p.parse().ok());
+        if pid.is_some_and(is_running) {
+            continue;
+        }
+
+        if remove(entry.path()) {
+            removed += 1;
+        }
+    }
+
+    if removed > 0 {
+        log::debug!("Removed {removed} stale libraries from {chaud_dir:?}");
+    }
+}
+
+/// Remove the save-temps artifacts (`*.rcgu.*`) produced alongside `objs`,
+/// except for `objs` themselves.
+///
+/// Every build of a crate produces a new set of artifacts, which all share the
+/// same prefix (e.g. `root-0123456789abcdef.`).
+pub fn save_temps<P: AsRef<Utf8Path>>(objs: &[P]) {
+    let Some(first) = objs.first().map(AsRef::as_ref) else {
+        return;
+    };
+
+    let (Some(dir), Some(name)) = (first.parent(), first.file_name()) else {
+        return;
+    };
+
+    let Some((prefix, _)) = name.split_once('.') else {
+        return;
+    };
+    let prefix = format!("{prefix}.");
+
+    let entries = match dir.read_dir_utf8() {
+        Ok(e) => e,
+        Err(e) => {
+            log::warn!("Failed to read {dir:?}: {e}");
+            return;
+        }
+    };
+
+    let mut removed = 0_usize;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if !name.starts_with(&prefix) || !name.contains(".rcgu.") {
+            continue;
+        }
+
+        if objs.iter().any(|o| o.as_ref() == entry.path()) {
+            continue;
+        }
+
+        if remove(entry.path()) {
+            removed += 1;
+        }
+    }
+
+    if removed > 0 {
+        log::trace!("Removed {removed} stale save-temps artifacts from {dir:?}");
+    }
+}
+
+/// Remove the file at `path`, returning `true` if it was removed.
+pub fn remove(path: &Utf8Path) -> bool {
+    log::trace!("Removing {path:?}");
+
+    match fs::remove_file(path) {
+        Ok(()) => true,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+        Err(e) => {
+            log::warn!("Failed to remove {path:?}: {e}");
+            false
+        }
+    }
+}
+
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // Don't send signals to process groups.
+    if pid <= 0 {
+        return false;
+    }
+
+    // SAFETY: Signal `0` only checks whether the process exists.
+    let res = unsafe { libc::kill(pid, 0) };
+
+    // `EPERM` indicates a running process owned by a different user.
+    res == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
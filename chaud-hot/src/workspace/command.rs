@@ -0,0 +1,99 @@
+//! Commands sent to the worker thread by the application.
+
+use anyhow::{Result, bail, ensure};
+use core::ffi::c_int;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering::Relaxed;
+use core::{mem, ptr};
+use parking_lot::Mutex;
+
+static QUEUE: Mutex<Vec<Command>> = Mutex::new(Vec::new());
+
+/// Set by the signal handler installed by [`install_signal`].
+static SIGNALED: AtomicBool = AtomicBool::new(false);
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Copy, Clone)]
+pub enum Command {
+    /// Revert every function to the state before the latest reload.
+    Rollback,
+    /// Reload now, regardless of the trigger mode or whether reloading is
+    /// paused.
+    Reload,
+}
+
+pub fn send(cmd: Command) {
+    log::trace!("Queueing command: {cmd:?}");
+    QUEUE.lock().push(cmd);
+}
+
+/// Take all queued commands, in the order they were sent.
+///
+/// A received reload signal counts as a [`Command::Reload`].
+pub fn take() -> Vec<Command> {
+    let mut cmds = mem::take(&mut *QUEUE.lock());
+
+    if SIGNALED.swap(false, Relaxed) {
+        log::debug!("Received reload signal");
+        cmds.push(Command::Reload);
+    }
+
+    cmds
+}
+
+/// Ask the worker to perform a rollback.
+pub fn rollback() {
+    send(Command::Rollback);
+}
+
+/// Ask the worker to reload.
+pub fn reload_now() {
+    send(Command::Reload);
+}
+
+pub fn pause() {
+    PAUSED.store(true, Relaxed);
+}
+
+pub fn resume() {
+    PAUSED.store(false, Relaxed);
+}
+
+pub(crate) fn is_paused() -> bool {
+    PAUSED.load(Relaxed)
+}
+
+/// Request a reload whenever the process receives the signal called `name`
+/// (e.g., `SIGUSR1`).
+pub(crate) fn install_signal(name: &str) -> Result<()> {
+    extern "C" fn handle(_: c_int) {
+        SIGNALED.store(true, Relaxed);
+    }
+
+    let signal = match name.strip_prefix("SIG").unwrap_or(name) {
+        "HUP" => libc::SIGHUP,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        _ => bail!("Unsupported reload signal: {name:?}"),
+    };
+
+    // SAFETY: All-zero is a valid `sigaction`, and `sa_mask` is initialized
+    // below.
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = handle as extern "C" fn(c_int) as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART;
+    // SAFETY: `sa_mask` is a valid, writable `sigset_t`.
+    unsafe { libc::sigemptyset(&raw mut action.sa_mask) };
+    // SAFETY: `action` is fully initialized, and the handler is
+    // async-signal-safe (it only stores an atomic).
+    let res = unsafe { libc::sigaction(signal, &raw const action, ptr::null_mut()) };
+    ensure!(
+        res == 0,
+        "Failed to install handler for {name}: {}",
+        std::io::Error::last_os_error()
+    );
+
+    log::debug!("Installed reload signal handler for {name}");
+    Ok(())
+}
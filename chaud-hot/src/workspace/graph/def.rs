@@ -1,17 +1,34 @@
 //! The **def**inition of the [`Graph`] type.
 
-use super::{BuildEnv, Krate, KrateIdx, KrateIndex};
+use super::layout::Layout;
+use super::{BuildEnv, FeatureFlags, Krate, KrateIdx, KrateIndex};
+use crate::Config;
 use crate::cargo::Cargo;
-use crate::cargo::metadata::{ManifestPath, Metadata};
+use crate::cargo::metadata::{ManifestPath, Node, Package, PackageId, PackageName};
 use crate::util::assert::err_assert;
+use crate::util::etx;
 use anyhow::{Context as _, Result};
+use camino::{Utf8Path, Utf8PathBuf};
 use core::ops;
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use std::collections::VecDeque;
 
 pub struct Graph {
     env: BuildEnv,
     krates: Box<[Krate]>,
+    index: KrateIndex,
+    workspace_root: Utf8PathBuf,
+    target_dir: Utf8PathBuf,
+    /// The configuration, including options from the root package's manifest.
+    config: Config,
+    /// The arguments the graph was created from, used by [`Graph::reload`].
+    args: Args,
+}
+
+struct Args {
+    root_mani: ManifestPath,
+    feature_flags: Option<&'static str>,
+    config: Config,
 }
 
 impl ops::Index<KrateIdx> for Graph {
@@ -30,14 +47,44 @@ impl Graph {
     pub fn new(
         root_mani: ManifestPath,
         feature_flags: Option<&'static str>,
+        config: Config,
     ) -> Result<&'static Self> {
-        new_inner(root_mani, feature_flags).context("Failed to load crate graph")
+        new_inner(root_mani, feature_flags, config).context("Failed to load crate graph")
+    }
+
+    /// Load the graph again, e.g. after a manifest changed.
+    ///
+    /// Like [`Graph::new`], this leaks the result, since the old graph may
+    /// still be referenced.
+    pub fn reload(&self) -> Result<&'static Self> {
+        let Args { root_mani, feature_flags, config } = &self.args;
+        Self::new(root_mani.clone(), *feature_flags, config.clone())
     }
 
     pub fn env(&self) -> &BuildEnv {
         &self.env
     }
 
+    pub fn workspace_root(&self) -> &Utf8Path {
+        &self.workspace_root
+    }
+
+    pub fn target_dir(&self) -> &Utf8Path {
+        &self.target_dir
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn get_pkg(&self, name: &PackageName) -> Option<&Krate> {
+        self.index.get_pkg(name).map(|k| &self[k])
+    }
+
+    pub fn get_id(&self, id: &PackageId) -> Option<&Krate> {
+        self.index.get_id(id).map(|k| &self[k])
+    }
+
     pub fn collect_krates_to_watch(&self) -> impl Iterator<Item = &Krate> {
         collect_inner(self).into_iter().map(|k| &self[k])
     }
@@ -46,22 +93,81 @@ impl Graph {
 fn new_inner(
     root_mani: ManifestPath,
     feature_flags: Option<&'static str>,
+    config: Config,
 ) -> Result<&'static Graph> {
-    let cargo = Cargo::new(root_mani);
-    let meta = cargo.load_metadata()?;
-
-    let index = KrateIndex::new(meta.packages())?;
-    let env = BuildEnv::new(cargo, feature_flags, &meta, &index)?;
-    let krates = load_krates(&meta, &env, &index)?;
-
-    Ok(Box::leak(Box::new(Graph { env, krates })))
+    let args = Args {
+        root_mani: root_mani.clone(),
+        feature_flags,
+        config: config.clone(),
+    };
+
+    let cargo = Cargo::new(root_mani.clone(), config.cargo.clone());
+    // Only the manifests are needed to read the configuration.
+    let manis = cargo.load_metadata(["--no-deps"])?;
+
+    let mani_config = manis
+        .packages()
+        .iter()
+        .find(|p| *p.manifest_path() == root_mani)
+        .map(Package::config)
+        .unwrap_or_default();
+    let config = config.or(mani_config);
+    log::trace!("{config:?}");
+
+    // The manifest may have configured a different cargo.
+    let cargo = Cargo::new(root_mani, config.cargo.clone());
+
+    // Resolve the dependencies for the features and platform of the build, so
+    // that only crates that are actually compiled are included.
+    let features = FeatureFlags::new(feature_flags)?;
+    let layout = Layout::detect();
+    let platform = config
+        .target
+        .as_deref()
+        .or(layout.as_ref().map(Layout::triple))
+        .unwrap_or("host-tuple");
+    let meta = cargo.load_metadata(features.args().chain(["--filter-platform", platform]))?;
+
+    let pkgs: Box<[_]> = meta
+        .packages()
+        .iter()
+        .filter(|p| p.is_path() || config.packages.iter().any(|n| n == p.name().as_str()))
+        .collect();
+
+    let index = KrateIndex::new(pkgs.iter().copied())?;
+    let env = BuildEnv::new(cargo, features, &config, layout.as_ref(), &meta, &index)?;
+    let krates = load_krates(&pkgs, meta.resolve(), &env, &index)?;
+    let workspace_root = meta.workspace_root().to_owned();
+    let target_dir = env.target_dir().to_owned();
+
+    Ok(Box::leak(Box::new(Graph {
+        env,
+        krates,
+        index,
+        workspace_root,
+        target_dir,
+        config,
+        args,
+    })))
 }
 
-fn load_krates(meta: &Metadata, env: &BuildEnv, index: &KrateIndex) -> Result<Box<[Krate]>> {
+/// Load the crates for `pkgs`, which are the path packages and the configured
+/// additional packages.
+fn load_krates(
+    pkgs: &[&Package],
+    resolve: &[Node],
+    env: &BuildEnv,
+    index: &KrateIndex,
+) -> Result<Box<[Krate]>> {
+    let nodes: HashMap<_, _> = resolve.iter().map(|n| (n.id(), n)).collect();
+
     let mut krates = vec![];
 
-    for pkg in meta.packages() {
-        krates.push(Krate::new(env, index, pkg)?);
+    for pkg in pkgs {
+        let node = nodes
+            .get(pkg.id())
+            .with_context(etx!("Package {} not found in the resolve", pkg.name()))?;
+        krates.push(Krate::new(env, index, pkg, node)?);
     }
 
     krates.sort_unstable_by_key(|k| k.idx());
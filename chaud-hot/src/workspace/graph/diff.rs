@@ -0,0 +1,87 @@
+//! Comparing a [`Graph`] with a reloaded one.
+
+use super::Graph;
+use crate::cargo::metadata::PackageName;
+use anyhow::{Result, ensure};
+use core::fmt;
+use hashbrown::HashSet;
+
+/// The differences between two compatible graphs.
+pub struct GraphDiff {
+    /// Packages that are watched only in the new graph.
+    added: Box<[PackageName]>,
+    /// Packages that are watched only in the old graph.
+    removed: Box<[PackageName]>,
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |pkgs: &[PackageName]| match pkgs.is_empty() {
+            true => "none".to_owned(),
+            false => pkgs
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+
+        write!(
+            f,
+            "watched crates added: {}; removed: {}",
+            list(&self.added),
+            list(&self.removed)
+        )
+    }
+}
+
+impl Graph {
+    /// Compare `self` with `new`, a reloaded version of `self`.
+    ///
+    /// Fails if `new` can't replace `self` without restarting the application,
+    /// e.g. because the build flags or configuration changed.
+    pub fn diff(&self, new: &Graph) -> Result<GraphDiff> {
+        let (old_env, new_env) = (self.env(), new.env());
+
+        ensure!(
+            self[old_env.root()].pkg() == new[new_env.root()].pkg(),
+            "The root package changed"
+        );
+        ensure!(old_env.bin() == new_env.bin(), "The root binary changed");
+        ensure!(
+            old_env.flags() == new_env.flags(),
+            "The build flags changed: {:?} -> {:?}",
+            old_env.flags(),
+            new_env.flags()
+        );
+        ensure!(
+            self.workspace_root() == new.workspace_root(),
+            "The workspace root changed"
+        );
+        ensure!(
+            self.target_dir() == new.target_dir(),
+            "The target directory changed"
+        );
+        ensure!(
+            self.config() == new.config(),
+            "The configuration in `[package.metadata.chaud]` changed"
+        );
+
+        let watched = |g: &Graph| -> HashSet<PackageName> {
+            g.collect_krates_to_watch()
+                .map(|k| k.pkg().clone())
+                .collect()
+        };
+        let (old, new) = (watched(self), watched(new));
+
+        let sorted = |s: HashSet<&PackageName>| -> Box<[PackageName]> {
+            let mut v: Vec<_> = s.into_iter().cloned().collect();
+            v.sort_unstable();
+            v.into()
+        };
+
+        Ok(GraphDiff {
+            added: sorted(new.difference(&old).collect()),
+            removed: sorted(old.difference(&new).collect()),
+        })
+    }
+}
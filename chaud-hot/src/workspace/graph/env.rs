@@ -1,4 +1,6 @@
+use super::layout::{self, Layout};
 use super::{KrateIdx, KrateIndex};
+use crate::Config;
 use crate::cargo::metadata::{Metadata, TargetKind, TargetName};
 use crate::cargo::{Cargo, StdioMode};
 use crate::util::assert::err_unreachable;
@@ -13,6 +15,10 @@ use std::{env, fs};
 pub struct BuildEnv {
     root: KrateIdx,
     bin: TargetName,
+    /// The kind of the `bin` target: [`TargetKind::Bin`] or
+    /// [`TargetKind::Example`].
+    bin_kind: TargetKind,
+    target_dir: Utf8PathBuf,
     chaud_dir: Utf8PathBuf,
     cargo: Cargo,
     flags: Box<[String]>,
@@ -20,14 +26,37 @@ pub struct BuildEnv {
     rustc_features_env: Option<String>,
 }
 
+/// The feature flags of the build, from `CHAUD_FEATURE_FLAGS`.
+#[derive(Debug)]
+pub(super) struct FeatureFlags {
+    flags: Vec<String>,
+    /// Feature flags from `chaud-rustc` that must be passed to cargo.
+    rustc_env: Option<String>,
+}
+
+impl FeatureFlags {
+    pub(super) fn new(ct_feature_flags: Option<&'static str>) -> Result<Self> {
+        new_features(ct_feature_flags).context("Failed to determine feature flags")
+    }
+
+    /// The arguments that select the features for `cargo`.
+    pub(super) fn args(&self) -> impl Iterator<Item = &str> {
+        ["-Fchaud/unsafe-hot-reload"]
+            .into_iter()
+            .chain(self.flags.iter().map(String::as_str))
+    }
+}
+
 impl BuildEnv {
     pub(super) fn new(
         cargo: Cargo,
-        feature_flags: Option<&'static str>,
+        features: FeatureFlags,
+        config: &Config,
+        layout: Option<&Layout>,
         meta: &Metadata,
         index: &KrateIndex,
     ) -> Result<Self> {
-        new_inner(cargo, feature_flags, meta, index).context("Failed to load build env")
+        new_inner(cargo, features, config, layout, meta, index).context("Failed to load build env")
     }
 
     pub fn root(&self) -> KrateIdx {
@@ -38,6 +67,14 @@ impl BuildEnv {
         &self.bin
     }
 
+    pub fn bin_kind(&self) -> TargetKind {
+        self.bin_kind
+    }
+
+    pub fn target_dir(&self) -> &Utf8Path {
+        &self.target_dir
+    }
+
     pub fn chaud_dir(&self) -> &Utf8Path {
         &self.chaud_dir
     }
@@ -56,16 +93,54 @@ impl BuildEnv {
     }
 }
 
-fn new_inner(
-    cargo: Cargo,
-    ct_feature_flags: Option<&'static str>,
+fn new_features(ct_feature_flags: Option<&'static str>) -> Result<FeatureFlags> {
+    let rt_feature_flags = match env::var("CHAUD_FEATURE_FLAGS") {
+        Ok(f) => Some(f),
+        Err(VarError::NotPresent) => None,
+        Err(VarError::NotUnicode(_)) => bail!("Invalid UTF-8 in CHAUD_FEATURE_FLAGS"),
+    };
+    let rt_feature_flags = rt_feature_flags.as_deref();
+
+    if let (Some(ct), Some(rt)) = (ct_feature_flags, rt_feature_flags) {
+        ensure!(
+            ct == rt,
+            "Compile-time and run-time CHAUD_FEATURE_FLAGS divereged. ct: {ct_feature_flags:?}, rt: {rt_feature_flags:?}"
+        );
+    }
+
+    let mut rustc_env = None;
+    if let (Some(ct), None) = (ct_feature_flags, rt_feature_flags) {
+        rustc_env = Some(ct.to_owned());
+    }
+
+    let flags = ct_feature_flags.or(rt_feature_flags).unwrap_or("");
+    let flags = shlex::split(flags).context("shlex of CHAUD_FEATURE_FLAGS failed")?;
+
+    Ok(FeatureFlags { flags, rustc_env })
+}
+
+/// Arguments from `cargo chaud`, which passes the `rustc` flags required by
+/// Chaud via `--config`, so that they are merged with the configured ones.
+fn cargo_chaud_args() -> Result<Vec<String>> {
+    match env::var("__CHAUD_CARGO_ARGS") {
+        Ok(a) => shlex::split(&a).context("shlex of __CHAUD_CARGO_ARGS failed"),
+        Err(VarError::NotPresent) => Ok(vec![]),
+        Err(VarError::NotUnicode(_)) => bail!("Invalid UTF-8 in __CHAUD_CARGO_ARGS"),
+    }
+}
+
+/// Find the target called `bin` of kind `bin_kind` in the root package.
+fn find_root(
+    cargo: &Cargo,
     meta: &Metadata,
     index: &KrateIndex,
-) -> Result<BuildEnv> {
-    let exe_file = Utf8PathBuf::try_from(std::env::current_exe()?)?;
-    let exe_dir = exe_file.parent().context("exe has no parent")?;
-
-    let bin = exe_file.file_stem().context("exe has no stem")?;
+    bin_kind: TargetKind,
+    bin: &str,
+) -> Result<(KrateIdx, TargetName)> {
+    let what = match bin_kind {
+        TargetKind::Example => "example",
+        _ => "bin",
+    };
 
     let mut root = None;
     for pkg in meta.packages() {
@@ -76,7 +151,7 @@ fn new_inner(
         ensure!(root.is_none(), "Multiple packages for {:?}", cargo.mani());
 
         for t in pkg.targets() {
-            if !t.kind().contains(&TargetKind::Bin) {
+            if !t.kind().contains(&bin_kind) {
                 continue;
             }
 
@@ -86,7 +161,7 @@ fn new_inner(
 
             ensure!(
                 root.is_none(),
-                "Multiple `bin` candidates for {bin:?} in {:?}",
+                "Multiple `{what}` candidates for {bin:?} in {:?}",
                 cargo.mani().path()
             );
 
@@ -99,71 +174,115 @@ fn new_inner(
 
         ensure!(
             root.is_some(),
-            "No `bin` target for {bin:?} in {:?}",
+            "No `{what}` target for {bin:?} in {:?}",
             cargo.mani().path()
         );
     }
-    let (root, bin) = root.with_context(etx!("No package for {:?}", cargo.mani().path()))?;
-
-    let mut profile = exe_dir
-        .components()
-        .next_back()
-        .context("missing profile component")?
-        .as_str();
-    if profile == "debug" {
-        profile = "dev";
-    }
+    root.with_context(etx!("No package for {:?}", cargo.mani().path()))
+}
 
-    let chaud_dir = exe_dir.join("chaud");
-    fs::create_dir_all(&chaud_dir)?;
+fn new_inner(
+    cargo: Cargo,
+    features: FeatureFlags,
+    config: &Config,
+    layout: Option<&Layout>,
+    meta: &Metadata,
+    index: &KrateIndex,
+) -> Result<BuildEnv> {
+    let exe_file = Utf8PathBuf::try_from(std::env::current_exe()?)?;
+    let exe_dir = exe_file.parent().context("exe has no parent")?;
 
-    let flags = [
-        "--bin",
-        bin.as_str(),
-        "--profile",
-        profile,
-        "-Fchaud/unsafe-hot-reload",
-    ];
+    let bin = exe_file.file_stem().context("exe has no stem")?;
 
-    let rt_feature_flags = match env::var("CHAUD_FEATURE_FLAGS") {
-        Ok(f) => Some(f),
-        Err(VarError::NotPresent) => None,
-        Err(VarError::NotUnicode(_)) => bail!("Invalid UTF-8 in CHAUD_FEATURE_FLAGS"),
+    // Examples are placed in the `examples` directory of the profile directory.
+    let (bin_kind, exe_profile_dir) = match exe_dir.file_name() {
+        Some("examples") => (
+            TargetKind::Example,
+            exe_dir.parent().context("examples dir has no parent")?,
+        ),
+        _ => (TargetKind::Bin, exe_dir),
     };
-    let rt_feature_flags = rt_feature_flags.as_deref();
 
-    if let (Some(ct), Some(rt)) = (ct_feature_flags, rt_feature_flags) {
-        ensure!(
-            ct == rt,
-            "Compile-time and run-time CHAUD_FEATURE_FLAGS divereged. ct: {:?}, rt: {:?}",
-            ct_feature_flags,
-            rt_feature_flags
+    let (root, bin) = find_root(&cargo, meta, index, bin_kind, bin)?;
+
+    // Prefer the layout of the build, which also works if the binary was
+    // moved, and fall back to the location of the binary otherwise.
+    let (target_dir, profile_dir) = match layout {
+        Some(l) => (l.target_dir(), l.profile_dir()),
+        None => {
+            log::debug!("Unknown build layout, using the location of the binary");
+            (meta.target_directory(), exe_profile_dir)
+        }
+    };
+    if target_dir != meta.target_directory() {
+        log::debug!(
+            "Building in {target_dir:?} instead of {:?}",
+            meta.target_directory()
         );
     }
 
-    let mut features_env = None;
-    if let (Some(ct), None) = (ct_feature_flags, rt_feature_flags) {
-        features_env = Some(ct.to_owned());
+    let mut profile = match layout.and_then(Layout::profile) {
+        Some(p) => p,
+        None => match exe_profile_dir.file_name() {
+            Some("debug") => "dev",
+            Some(p) => p,
+            None => bail!("missing profile component"),
+        },
+    };
+    if let Some(p) = &config.profile {
+        profile = p;
     }
 
-    let feature_flags = ct_feature_flags.or(rt_feature_flags).unwrap_or("");
+    let target = config
+        .target
+        .as_deref()
+        .or(layout.and_then(Layout::explicit_target));
 
-    let feature_flags =
-        shlex::split(feature_flags).context("shlex of CHAUD_FEATURE_FLAGS failed")?;
+    // A configured profile or target is built into a different directory.
+    let profile_dir = match config.profile.is_some() || config.target.is_some() {
+        true => layout::profile_dir(target_dir, target, profile),
+        false => profile_dir.to_owned(),
+    };
+
+    let chaud_dir = profile_dir.join("chaud");
+    fs::create_dir_all(&chaud_dir)?;
+
+    let bin_flag = match bin_kind {
+        TargetKind::Example => "--example",
+        _ => "--bin",
+    };
+
+    let flags = [
+        bin_flag,
+        bin.as_str(),
+        "--profile",
+        profile,
+        "--target-dir",
+        target_dir.as_str(),
+    ];
+
+    let target = target
+        .into_iter()
+        .flat_map(|t| ["--target".to_owned(), t.to_owned()]);
 
     let flags = flags
         .into_iter()
+        .chain(features.args())
         .map(|s| s.to_owned())
-        .chain(feature_flags)
+        .chain(target)
+        .chain(cargo_chaud_args()?)
+        .chain(config.cargo_args.iter().cloned())
         .collect();
 
     let this = BuildEnv {
         root,
         bin,
+        bin_kind,
+        target_dir: target_dir.to_owned(),
         chaud_dir,
         cargo,
         flags,
-        rustc_features_env: features_env,
+        rustc_features_env: features.rustc_env,
     };
 
     log::trace!("{this:?}");
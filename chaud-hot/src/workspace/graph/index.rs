@@ -1,4 +1,4 @@
-use crate::cargo::metadata::{Package, PackageName};
+use crate::cargo::metadata::{Package, PackageId, PackageName};
 use crate::util::CfgInto as _;
 use anyhow::{Context as _, Result, bail};
 use hashbrown::{HashMap, hash_map};
@@ -13,20 +13,27 @@ impl KrateIdx {
     pub fn usize(self) -> usize {
         self.0.cfg_into()
     }
+
+    #[cfg(test)]
+    pub(crate) const fn new_test(idx: u32) -> Self {
+        Self(idx)
+    }
 }
 
-/// An index of all crates, mapping [`PackageName`] to [`KrateIdx`].
+/// An index of all crates, mapping [`PackageName`] and [`PackageId`] to
+/// [`KrateIdx`].
 pub struct KrateIndex {
     by_pkg: HashMap<PackageName, KrateIdx>,
+    by_id: HashMap<PackageId, KrateIdx>,
 }
 
 impl KrateIndex {
-    pub fn new(pkgs: &[Package]) -> Result<Self> {
-        let mut pkgs: Vec<_> = pkgs.iter().collect();
+    pub fn new<'a>(pkgs: impl IntoIterator<Item = &'a Package>) -> Result<Self> {
+        let mut pkgs: Vec<_> = pkgs.into_iter().collect();
         // Do not depend on Cargo's output order for determinism.
         pkgs.sort_unstable_by_key(|p| p.name());
 
-        let mut this = Self { by_pkg: HashMap::new() };
+        let mut this = Self { by_pkg: HashMap::new(), by_id: HashMap::new() };
 
         for pkg in pkgs {
             this.insert(pkg).context("Failed to build package index")?;
@@ -49,6 +56,7 @@ impl KrateIndex {
             hash_map::Entry::Occupied(entry) => bail!("Duplicate package name: {:?}", entry.key()),
             hash_map::Entry::Vacant(entry) => entry.insert(next),
         };
+        self.by_id.insert(pkg.id().clone(), next);
 
         Ok(())
     }
@@ -56,4 +64,8 @@ impl KrateIndex {
     pub fn get_pkg(&self, name: &PackageName) -> Option<KrateIdx> {
         self.by_pkg.get(name).copied()
     }
+
+    pub fn get_id(&self, id: &PackageId) -> Option<KrateIdx> {
+        self.by_id.get(id).copied()
+    }
 }
@@ -1,6 +1,6 @@
 use super::{BuildEnv, KrateIdx, KrateIndex};
 use crate::cargo::metadata::{
-    Dependency, DependencyKind, Package, PackageName, Target, TargetKind, TargetName,
+    DependencyKind, Node, Package, PackageName, Target, TargetKind, TargetName,
 };
 use crate::util::etx;
 use anyhow::{Context as _, Result, ensure};
@@ -27,6 +27,8 @@ pub struct Krate {
     pkg: PackageName,
     deps: Box<[KrateIdx]>,
     dirs: Box<[KrateDir]>,
+    /// The `rustc` crate names of the relevant targets.
+    names: Box<[String]>,
 }
 
 impl fmt::Display for Krate {
@@ -36,8 +38,13 @@ impl fmt::Display for Krate {
 }
 
 impl Krate {
-    pub(super) fn new(env: &BuildEnv, index: &KrateIndex, pkg: &Package) -> Result<Self> {
-        new_inner(env, index, pkg)
+    pub(super) fn new(
+        env: &BuildEnv,
+        index: &KrateIndex,
+        pkg: &Package,
+        node: &Node,
+    ) -> Result<Self> {
+        new_inner(env, index, pkg, node)
             .with_context(etx!("Failed to build crate info for {}", pkg.name()))
     }
 
@@ -52,42 +59,74 @@ impl Krate {
     pub fn dirs(&self) -> &[KrateDir] {
         &self.dirs
     }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn pkg(&self) -> &PackageName {
+        &self.pkg
+    }
+
+    /// The directory containing the crate's manifest, if the crate is watched.
+    pub fn root_dir(&self) -> Option<&Utf8Path> {
+        self.dirs.iter().find_map(|d| match d {
+            KrateDir::Root(p) => Some(p.as_path()),
+            KrateDir::Src(_) => None,
+        })
+    }
 }
 
-fn new_inner(env: &BuildEnv, index: &KrateIndex, package: &Package) -> Result<Krate> {
+fn new_inner(env: &BuildEnv, index: &KrateIndex, package: &Package, node: &Node) -> Result<Krate> {
     let pkg = package.name().clone();
     let idx = index
         .get_pkg(&pkg)
         .context("Package not found in the index")?;
 
-    let deps = filter_deps(index, package.dependencies());
-
     let root_bin = match env.root() == idx {
-        true => Some(env.bin()),
+        true => Some((env.bin_kind(), env.bin())),
         false => None,
     };
+
+    // Examples may use the dev-dependencies of their package.
+    let dev = root_bin.is_some_and(|(kind, _)| kind == TargetKind::Example);
+    let deps = filter_deps(index, node, dev);
+    log::trace!("{pkg}: features {:?}, deps {deps:?}", node.features());
+
     let dirs = krate_dirs(root_bin, package).context("Failed to determine crate dirs")?;
+    let names = krate_names(root_bin, package)?;
 
-    Ok(Krate { idx, pkg, deps, dirs })
+    Ok(Krate { idx, pkg, deps, dirs, names })
 }
 
-fn filter_deps(index: &KrateIndex, deps: &[Dependency]) -> Box<[KrateIdx]> {
-    let follow = [DependencyKind::Normal, DependencyKind::Build];
+/// The dependencies of `node` that are compiled into the binary, i.e. those
+/// resolved for the enabled features and the target platform, excluding
+/// dev-dependencies unless `dev` is set.
+fn filter_deps(index: &KrateIndex, node: &Node, dev: bool) -> Box<[KrateIdx]> {
+    let follow: &[_] = match dev {
+        true => &[
+            DependencyKind::Normal,
+            DependencyKind::Build,
+            DependencyKind::Dev,
+        ],
+        false => &[DependencyKind::Normal, DependencyKind::Build],
+    };
 
-    let mut deps: Vec<_> = deps
+    let mut deps: Vec<_> = node
+        .deps()
         .iter()
-        .filter(|k| follow.contains(&k.kind()))
-        .filter_map(|d| index.get_pkg(d.name()))
+        .filter(|d| d.kinds().any(|k| follow.contains(&k)))
+        .filter_map(|d| index.get_id(d.pkg()))
         .collect();
     deps.sort_unstable();
-    // In case the same dependency shows up multiple times, e.g. as a "build"
-    // and "normal" dependency.
-    deps.dedup();
 
     deps.into_boxed_slice()
 }
 
-fn krate_dirs(root_bin: Option<&TargetName>, pkg: &Package) -> Result<Box<[KrateDir]>> {
+fn krate_dirs(
+    root_bin: Option<(TargetKind, &TargetName)>,
+    pkg: &Package,
+) -> Result<Box<[KrateDir]>> {
     let targets = filter_targets(root_bin, pkg.targets())?;
     let Some(targets) = targets else {
         return Ok(Box::new([]));
@@ -118,8 +157,22 @@ fn krate_dirs(root_bin: Option<&TargetName>, pkg: &Package) -> Result<Box<[Krate
     Ok(dirs.into_boxed_slice())
 }
 
+fn krate_names(
+    root_bin: Option<(TargetKind, &TargetName)>,
+    pkg: &Package,
+) -> Result<Box<[String]>> {
+    let Some(targets) = filter_targets(root_bin, pkg.targets())? else {
+        return Ok(Box::new([]));
+    };
+
+    Ok(targets
+        .filter(|t| !t.kind().contains(&TargetKind::CustomBuild))
+        .map(|t| t.name().as_str().replace('-', "_"))
+        .collect())
+}
+
 fn filter_targets<'a>(
-    root_bin: Option<&TargetName>,
+    root_bin: Option<(TargetKind, &TargetName)>,
     targets: &'a [Target],
 ) -> Result<Option<impl Iterator<Item = &'a Target>>> {
     let is_rlib = |tk| [TargetKind::Lib, TargetKind::RLib].contains(tk);
@@ -129,8 +182,8 @@ fn filter_targets<'a>(
     let mut build = None;
 
     for target in targets {
-        if let Some(root_bin) = root_bin {
-            if target.kind().contains(&TargetKind::Bin) && target.name() == root_bin {
+        if let Some((kind, name)) = root_bin {
+            if target.kind().contains(&kind) && target.name() == name {
                 ensure!(bin.is_none(), "Multiple root bin targets");
                 bin = Some(target);
             }
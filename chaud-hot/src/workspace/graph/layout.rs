@@ -0,0 +1,156 @@
+//! The directory **layout** of the build that produced the running binary.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Where the running binary was built, as captured at compile time by the
+/// build script of this crate. Since this crate is a dependency of the binary,
+/// it is built with the same target dir, profile and target triple.
+#[derive(Debug)]
+pub(super) struct Layout {
+    target_dir: Utf8PathBuf,
+    /// The directory of the profile, e.g. `target/debug` or
+    /// `target/<triple>/release`.
+    profile_dir: Utf8PathBuf,
+    /// The target triple the binary was built for.
+    triple: &'static str,
+    /// Whether the triple was passed explicitly via `--target`, which changes
+    /// the location of the profile dir.
+    explicit_target: bool,
+}
+
+impl Layout {
+    /// Determine the layout from the build script's `OUT_DIR`, which is
+    /// `<target-dir>/[<triple>/]<profile>/build/<pkg>-<hash>/out`.
+    ///
+    /// Returns `None` if the `OUT_DIR` does not have the expected shape, e.g.
+    /// because Cargo's `build-dir` is separate from the target dir.
+    pub(super) fn detect() -> Option<Self> {
+        let this = from_out_dir(
+            Utf8Path::new(env!("__CHAUD_OUT_DIR")),
+            env!("__CHAUD_TARGET"),
+        );
+        log::trace!("{this:?}");
+        this
+    }
+
+    pub(super) fn target_dir(&self) -> &Utf8Path {
+        &self.target_dir
+    }
+
+    pub(super) fn profile_dir(&self) -> &Utf8Path {
+        &self.profile_dir
+    }
+
+    pub(super) fn triple(&self) -> &'static str {
+        self.triple
+    }
+
+    /// The triple to pass to cargo via `--target`, if any.
+    pub(super) fn explicit_target(&self) -> Option<&'static str> {
+        self.explicit_target.then_some(self.triple)
+    }
+
+    /// The name of the profile. Custom profiles are placed in a directory
+    /// with their name, while the built-in ones share `debug` (`dev` and
+    /// `test`) and `release` (`release` and `bench`). For those, the more
+    /// common profile is assumed, since the directory doesn't tell.
+    pub(super) fn profile(&self) -> Option<&str> {
+        match self.profile_dir.file_name()? {
+            "debug" => Some("dev"),
+            p => Some(p),
+        }
+    }
+}
+
+/// The directory Cargo builds `profile` into, when building for `target` (if
+/// passed via `--target`).
+pub(super) fn profile_dir(
+    target_dir: &Utf8Path,
+    target: Option<&str>,
+    profile: &str,
+) -> Utf8PathBuf {
+    let name = match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        p => p,
+    };
+
+    let mut dir = target_dir.to_owned();
+    dir.extend(target);
+    dir.push(name);
+    dir
+}
+
+fn from_out_dir(out_dir: &Utf8Path, triple: &'static str) -> Option<Layout> {
+    if out_dir.file_name()? != "out" {
+        return None;
+    }
+
+    let build_dir = out_dir.parent()?.parent()?;
+    if build_dir.file_name()? != "build" {
+        return None;
+    }
+
+    let profile_dir = build_dir.parent()?;
+    let parent = profile_dir.parent()?;
+
+    let explicit_target = parent.file_name()? == triple;
+    let target_dir = match explicit_target {
+        true => parent.parent()?,
+        false => parent,
+    };
+
+    Some(Layout {
+        target_dir: target_dir.to_owned(),
+        profile_dir: profile_dir.to_owned(),
+        triple,
+        explicit_target,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TRIPLE: &str = "x86_64-unknown-linux-gnu";
+
+    fn layout(out_dir: &str) -> Option<Layout> {
+        from_out_dir(Utf8Path::new(out_dir), TRIPLE)
+    }
+
+    #[test]
+    fn detect() {
+        let l = layout("/ws/target/debug/build/chaud-hot-0123abcd/out");
+        let l = l.as_ref();
+        assert_eq!(l.map(Layout::target_dir), Some(Utf8Path::new("/ws/target")));
+        assert_eq!(l.and_then(Layout::profile), Some("dev"));
+        assert_eq!(l.and_then(Layout::explicit_target), None);
+
+        let l = layout("/ws/target/x86_64-unknown-linux-gnu/fast/build/chaud-hot-0123abcd/out");
+        let l = l.as_ref();
+        assert_eq!(l.map(Layout::target_dir), Some(Utf8Path::new("/ws/target")));
+        assert_eq!(l.and_then(Layout::profile), Some("fast"));
+        assert_eq!(l.and_then(Layout::explicit_target), Some(TRIPLE));
+
+        assert!(layout("/build/chaud-hot-0123abcd/out").is_none());
+        assert!(layout("/ws/target/debug/build/chaud-hot-0123abcd").is_none());
+    }
+
+    #[test]
+    fn profile_dirs() {
+        let dir = |target, profile| profile_dir(Utf8Path::new("/t"), target, profile);
+
+        assert_eq!(dir(None, "dev"), "/t/debug");
+        assert_eq!(dir(None, "test"), "/t/debug");
+        assert_eq!(dir(None, "release"), "/t/release");
+        assert_eq!(dir(None, "bench"), "/t/release");
+        // Custom profiles use their own name, even if they inherit from a
+        // built-in one.
+        assert_eq!(dir(None, "fast"), "/t/fast");
+        assert_eq!(
+            dir(Some(TRIPLE), "dev"),
+            "/t/x86_64-unknown-linux-gnu/debug"
+        );
+    }
+}
@@ -4,6 +4,8 @@ pub use self::index::*;
 pub use self::krate::*;
 
 mod def;
+mod diff;
 mod env;
 mod index;
 mod krate;
+mod layout;
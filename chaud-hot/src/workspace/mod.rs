@@ -1,4 +1,6 @@
 mod watcher;
 
+pub mod clean;
+pub mod command;
 pub mod graph;
 pub mod worker;
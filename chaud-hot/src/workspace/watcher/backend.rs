@@ -0,0 +1,22 @@
+//! The backends used to watch for file changes: the native one (e.g.
+//! `inotify`), or polling.
+
+use super::EvHandler;
+use core::time::Duration;
+use notify::{PollWatcher, RecommendedWatcher, Watcher as _};
+
+/// The polling interval used when falling back from the native backend.
+pub(super) const FALLBACK_POLL: Duration = Duration::from_secs(1);
+
+pub(super) type Backend = Box<dyn notify::Watcher>;
+
+pub(super) fn native(handler: EvHandler) -> notify::Result<Backend> {
+    let w = RecommendedWatcher::new(handler, notify::Config::default())?;
+    Ok(Box::new(w))
+}
+
+pub(super) fn poll(handler: EvHandler, interval: Duration) -> notify::Result<Backend> {
+    let config = notify::Config::default().with_poll_interval(interval);
+    let w = PollWatcher::new(handler, config)?;
+    Ok(Box::new(w))
+}
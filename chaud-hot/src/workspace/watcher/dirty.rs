@@ -0,0 +1,82 @@
+use crate::workspace::graph::{Graph, KrateIdx};
+use hashbrown::HashSet;
+
+/// The crates affected by file changes.
+#[derive(Debug, Default)]
+pub struct Dirty {
+    krates: HashSet<KrateIdx>,
+    /// Whether a change could not be attributed to any crate, e.g. a change to
+    /// the workspace's `Cargo.lock`.
+    other: bool,
+    /// Whether a `Cargo.toml` or `Cargo.lock` changed.
+    manifest: bool,
+}
+
+impl Dirty {
+    pub fn is_empty(&self) -> bool {
+        self.krates.is_empty() && !self.other
+    }
+
+    pub fn extend(&mut self, other: Dirty) {
+        self.krates.extend(other.krates);
+        self.other |= other.other;
+        self.manifest |= other.manifest;
+    }
+
+    /// Whether a manifest changed, which may require reloading the graph.
+    pub fn manifest_changed(&self) -> bool {
+        self.manifest
+    }
+
+    /// Translate the crates from `old` to `new`, a reloaded graph. Crates
+    /// that no longer exist count as other files.
+    #[must_use]
+    pub fn remap(self, old: &Graph, new: &Graph) -> Self {
+        let mut this = Dirty {
+            krates: HashSet::new(),
+            other: self.other,
+            manifest: self.manifest,
+        };
+
+        for k in self.krates {
+            match new.get_pkg(old[k].pkg()) {
+                Some(krate) => this.add(krate.idx()),
+                None => this.add_other(),
+            }
+        }
+
+        this
+    }
+
+    pub(super) fn add(&mut self, krate: KrateIdx) {
+        self.krates.insert(krate);
+    }
+
+    pub(super) fn add_other(&mut self) {
+        self.other = true;
+    }
+
+    pub(super) fn add_manifest(&mut self) {
+        self.manifest = true;
+    }
+
+    /// Forget about manifest changes, once they have been handled.
+    pub fn clear_manifest(&mut self) {
+        self.manifest = false;
+    }
+
+    /// A human-readable list of the affected crates.
+    pub fn describe(&self, graph: &Graph) -> String {
+        let mut krates: Vec<_> = self.krates.iter().map(|&k| graph[k].to_string()).collect();
+        krates.sort_unstable();
+
+        if self.other {
+            krates.push("other files".to_owned());
+        }
+
+        match krates.is_empty() {
+            true => "nothing".to_owned(),
+            false => krates.join(", "),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Inputs of the build that may be outside of the crate directories.
+
+use crate::cargo::build_script::Executed;
+use crate::cargo::dep_info;
+use crate::workspace::graph::{Graph, KrateIdx};
+use camino::Utf8PathBuf;
+use hashbrown::{HashMap, HashSet};
+use notify::RecursiveMode;
+use std::path::{Path, PathBuf};
+
+/// The files read by the compiler (from dep-info), and the files and
+/// directories declared by build scripts via `rerun-if-changed`.
+#[derive(Debug, Default)]
+pub(super) struct Inputs {
+    /// The files, and the crates they are inputs of.
+    files: HashMap<PathBuf, Vec<KrateIdx>>,
+    /// Directories in which every change is relevant.
+    trees: Vec<(PathBuf, KrateIdx)>,
+    /// Set if a build failed since the inputs were collected, in which case
+    /// they may lack files the fixed build will read (e.g. a module that was
+    /// declared before its file was created).
+    outdated: bool,
+}
+
+impl Inputs {
+    /// Whether the inputs describe the latest build. Otherwise, every source
+    /// file may be relevant.
+    pub(super) fn is_complete(&self) -> bool {
+        let empty = self.files.is_empty() && self.trees.is_empty();
+        !self.outdated && !empty
+    }
+
+    pub(super) fn set_outdated(&mut self) {
+        self.outdated = true;
+    }
+
+    pub(super) fn add_file(&mut self, path: PathBuf, krate: KrateIdx) {
+        let krates = self.files.entry(path).or_default();
+        if !krates.contains(&krate) {
+            krates.push(krate);
+        }
+    }
+
+    /// The crates that `path` is an input of.
+    pub(super) fn krates_of(&self, path: &Path) -> Vec<KrateIdx> {
+        let mut krates = self.files.get(path).cloned().unwrap_or_default();
+        for (tree, krate) in &self.trees {
+            if path.starts_with(tree) {
+                krates.push(*krate);
+            }
+        }
+        krates
+    }
+
+    /// The directories that must be watched to observe changes to the inputs.
+    pub(super) fn dirs(&self) -> HashMap<PathBuf, RecursiveMode> {
+        let mut dirs: HashMap<_, _> = self
+            .files
+            .keys()
+            .filter_map(|f| f.parent())
+            .map(|d| (d.to_owned(), RecursiveMode::NonRecursive))
+            .collect();
+        for (tree, _) in &self.trees {
+            dirs.insert(tree.clone(), RecursiveMode::Recursive);
+        }
+        dirs
+    }
+
+    pub(super) fn summary(&self) -> (usize, usize) {
+        (self.files.len(), self.trees.len())
+    }
+}
+
+/// Collect the inputs of the crates in `names` (by crate name), from the
+/// `dep_infos` of the latest build and the outputs of the `build_scripts` it
+/// ran.
+pub(super) fn collect(
+    graph: &Graph,
+    names: &HashMap<&str, KrateIdx>,
+    dep_infos: &[Utf8PathBuf],
+    build_scripts: &[Executed],
+) -> Inputs {
+    let root = graph.workspace_root();
+
+    let mut inputs = Inputs::default();
+    let mut add = |krate: KrateIdx, path: Utf8PathBuf| {
+        // Generated files, which we'd otherwise modify ourselves.
+        if path.starts_with(graph.target_dir()) {
+            return;
+        }
+
+        if path.is_dir() {
+            inputs.trees.push((path.into_std_path_buf(), krate));
+        } else {
+            inputs.add_file(path.into_std_path_buf(), krate);
+        }
+    };
+
+    for path in dep_infos {
+        let Some(&krate) = dep_info::crate_name(path).and_then(|n| names.get(n)) else {
+            continue;
+        };
+
+        match dep_info::parse(path, root) {
+            Ok(files) => files.into_iter().for_each(|f| add(krate, f)),
+            Err(e) => log::warn!("Failed to parse dep-info: {e:#}"),
+        }
+    }
+
+    let watched: HashSet<_> = graph.collect_krates_to_watch().map(|k| k.idx()).collect();
+    for executed in build_scripts {
+        let Some(krate) = graph.get_id(&executed.package_id) else {
+            continue;
+        };
+        let Some(pkg_dir) = krate.root_dir() else {
+            continue;
+        };
+        if !watched.contains(&krate.idx()) {
+            continue;
+        }
+
+        let rerun = match executed.rerun_if(pkg_dir) {
+            Ok(r) => r,
+            Err(e) => {
+                log::warn!("Failed to read build script output of {krate}: {e:#}");
+                continue;
+            }
+        };
+
+        if !rerun.env_changed.is_empty() {
+            // The environment is inherited from this process, so any
+            // changes would require a restart anyway.
+            log::trace!(
+                "Build script of {krate} depends on env vars: {:?}",
+                rerun.env_changed
+            );
+        }
+        rerun.changed.into_iter().for_each(|f| add(krate.idx(), f));
+    }
+
+    inputs
+}
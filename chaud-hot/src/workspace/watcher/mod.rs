@@ -0,0 +1,716 @@
+use self::backend::{Backend, FALLBACK_POLL};
+pub use self::dirty::Dirty;
+use self::inputs::Inputs;
+use super::graph::{Graph, KrateDir, KrateIdx};
+use crate::cargo::build_script::Executed;
+use crate::util::glob::Glob;
+use crate::util::latest::{LatestPublisher, LatestReader, make_latest};
+use anyhow::{Context as _, Result, ensure};
+use camino::{Utf8Path, Utf8PathBuf};
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering::Relaxed;
+use core::time::Duration;
+use core::{mem, ops};
+use hashbrown::HashMap;
+use notify::{EventHandler, EventKind, RecursiveMode};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use std::{fs, io};
+
+mod backend;
+mod dirty;
+mod inputs;
+
+pub struct Watcher {
+    inner: Backend,
+    /// The polling interval, if polling.
+    polling: Option<Duration>,
+    /// Used to create a new backend when falling back to polling.
+    handler: EvHandler,
+    latest: LatestReader<Instant>,
+    graph: &'static Graph,
+    /// The paths that are always watched.
+    base: Box<[(PathBuf, RecursiveMode)]>,
+    /// The crates whose inputs are tracked, by crate name.
+    names: HashMap<&'static str, KrateIdx>,
+    /// The latest inputs, shared with the handler.
+    inputs: Arc<Mutex<Inputs>>,
+    /// The crates changed since the last [`Self::take_dirty`], shared with the
+    /// handler.
+    dirty: Arc<Mutex<Dirty>>,
+    /// Directories watched only because they contain inputs.
+    input_dirs: HashMap<PathBuf, RecursiveMode>,
+}
+
+impl ops::Deref for Watcher {
+    type Target = LatestReader<Instant>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.latest
+    }
+}
+
+impl ops::DerefMut for Watcher {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.latest
+    }
+}
+
+impl Watcher {
+    pub fn new(graph: &'static Graph) -> Result<Self> {
+        new_inner(graph).context("Failed to create watcher")
+    }
+
+    /// Switch to `graph`, a reloaded version of the current graph, watching
+    /// the directories of new crates and unwatching those of removed ones.
+    pub fn refresh(&mut self, graph: &'static Graph) -> Result<()> {
+        let dirs = extract_dirs(graph)?;
+        let base = base_paths(graph.workspace_root(), &dirs, &self.handler.extra);
+
+        *self.handler.scope.lock() = Scope::new(graph, &dirs);
+        // The inputs refer to the crates of the old graph. They are tracked
+        // again after the next build.
+        *self.inputs.lock() = Inputs::default();
+        self.names = krate_names(graph);
+        self.graph = graph;
+        let old = mem::replace(&mut self.base, base);
+
+        for (path, mode) in old.iter().filter(|p| !self.base.contains(p)) {
+            match self.inner.unwatch(path) {
+                Ok(()) => log::trace!("Unwatching: {path:?} ({mode:?})"),
+                Err(e) => log::trace!("Failed to unwatch {path:?}: {e}"),
+            }
+        }
+        let added: Vec<_> = self
+            .base
+            .iter()
+            .filter(|p| !old.contains(p))
+            .cloned()
+            .collect();
+        for (path, mode) in added {
+            match self.inner.watch(&path, mode) {
+                Ok(()) => log::trace!("Watching: {path:?} ({mode:?})"),
+                Err(e) if self.polling.is_none() => {
+                    log::warn!("Failed to watch {path:?}, falling back to polling: {e}");
+                    self.fall_back();
+                    break;
+                }
+                Err(e) => log::warn!("Failed to watch {path:?}: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take the crates affected by the changes published so far.
+    pub fn take_dirty(&mut self) -> Dirty {
+        mem::take(&mut *self.dirty.lock())
+    }
+
+    /// Track the inputs of the watched crates that may be outside the crate
+    /// directories, e.g. files read via `include_str!` or `#[path]` (from
+    /// `dep_infos`), and files declared by build scripts via
+    /// `rerun-if-changed`. Replaces any previously tracked inputs.
+    ///
+    /// If the native backend overflowed since the last call, this also falls
+    /// back to polling.
+    pub fn track_inputs(&mut self, dep_infos: &[Utf8PathBuf], build_scripts: &[Executed]) {
+        if self.polling.is_none() && self.handler.overflowed.load(Relaxed) {
+            log::warn!("The file watcher overflowed, falling back to polling");
+            self.fall_back();
+        }
+
+        let inputs = inputs::collect(self.graph, &self.names, dep_infos, build_scripts);
+
+        let mut dirs = inputs.dirs();
+        dirs.retain(|d, _| !self.is_watched(d));
+
+        for (dir, mode) in &self.input_dirs {
+            if dirs.get(dir) == Some(mode) {
+                continue;
+            }
+            match self.inner.unwatch(dir) {
+                Ok(()) => log::trace!("Unwatching: {dir:?} (input)"),
+                Err(e) => log::trace!("Failed to unwatch {dir:?}: {e}"),
+            }
+        }
+        let mut failed = false;
+        for (dir, mode) in &dirs {
+            if self.input_dirs.get(dir) == Some(mode) {
+                continue;
+            }
+            match self.inner.watch(dir, *mode) {
+                Ok(()) => log::trace!("Watching: {dir:?} ({mode:?}, input)"),
+                Err(e) if self.polling.is_none() => {
+                    log::warn!("Failed to watch {dir:?}, falling back to polling: {e}");
+                    failed = true;
+                    break;
+                }
+                Err(e) => log::warn!("Failed to watch {dir:?}: {e}"),
+            }
+        }
+
+        let (files, trees) = inputs.summary();
+        log::debug!(
+            "Tracking {files} input files and {trees} input dirs ({} additional watches)",
+            dirs.len()
+        );
+
+        self.input_dirs = dirs;
+        *self.inputs.lock() = inputs;
+
+        if failed {
+            self.fall_back();
+        }
+    }
+
+    /// Record that a build failed, so that the tracked inputs may be missing
+    /// files that the next build reads.
+    pub fn build_failed(&mut self) {
+        self.inputs.lock().set_outdated();
+    }
+
+    /// Register all watched paths with the backend, falling back to polling if
+    /// the native backend fails.
+    fn watch_all(&mut self) {
+        let paths: Vec<_> = self
+            .base
+            .iter()
+            .cloned()
+            .chain(self.input_dirs.iter().map(|(p, m)| (p.clone(), *m)))
+            .collect();
+        for (path, mode) in paths {
+            match self.inner.watch(&path, mode) {
+                Ok(()) => log::trace!("Watching: {path:?} ({mode:?})"),
+                Err(e) if matches!(e.kind, notify::ErrorKind::PathNotFound) => {
+                    log::warn!("Failed to watch {path:?}: {e}");
+                }
+                Err(e) if self.polling.is_none() => {
+                    log::warn!("Failed to watch {path:?}, falling back to polling: {e}");
+                    return self.fall_back();
+                }
+                Err(e) => log::warn!("Failed to watch {path:?}: {e}"),
+            }
+        }
+
+        log::debug!(
+            "Watching {} paths ({})",
+            self.base.len() + self.input_dirs.len(),
+            match self.polling {
+                Some(_) => "polling",
+                None => "native",
+            }
+        );
+    }
+
+    /// Replace the native backend by polling.
+    fn fall_back(&mut self) {
+        match backend::poll(self.handler.clone(), FALLBACK_POLL) {
+            Ok(b) => {
+                self.inner = b;
+                self.polling = Some(FALLBACK_POLL);
+                self.watch_all();
+            }
+            Err(e) => log::warn!("Failed to create polling watcher: {e}"),
+        }
+    }
+
+    fn is_watched(&self, dir: &Path) -> bool {
+        self.base.iter().any(|(path, mode)| match mode {
+            RecursiveMode::Recursive => dir.starts_with(path),
+            RecursiveMode::NonRecursive => dir == path,
+        })
+    }
+}
+
+fn new_inner(graph: &'static Graph) -> Result<Watcher> {
+    let (publisher, reader) = make_latest(Instant::now());
+
+    let dirs = extract_dirs(graph)?;
+
+    let config = graph.config();
+    let root = graph.workspace_root();
+
+    let extra: Box<[_]> = config
+        .watch
+        .iter()
+        .map(|p| root.as_std_path().join(p))
+        .collect();
+
+    let handler = EvHandler {
+        latest: publisher,
+        inputs: Arc::default(),
+        dirty: Arc::default(),
+        scope: Arc::new(Mutex::new(Scope::new(graph, &dirs))),
+        overflowed: Arc::default(),
+        reported_event_err: false,
+        root: root.to_owned(),
+        assets: config.assets.iter().map(|p| Glob::new(p)).collect(),
+        extra: extra.clone(),
+    };
+
+    let mut polling = config.poll;
+    let inner = match polling {
+        Some(interval) => backend::poll(handler.clone(), interval)?,
+        None => match backend::native(handler.clone()) {
+            Ok(b) => b,
+            Err(e) => {
+                log::warn!("Failed to create native watcher, falling back to polling: {e}");
+                polling = Some(FALLBACK_POLL);
+                backend::poll(handler.clone(), FALLBACK_POLL)?
+            }
+        },
+    };
+
+    let base = base_paths(root, &dirs, &extra);
+    let names = krate_names(graph);
+
+    let mut watcher = Watcher {
+        inner,
+        polling,
+        latest: reader,
+        inputs: Arc::clone(&handler.inputs),
+        dirty: Arc::clone(&handler.dirty),
+        handler,
+        graph,
+        base,
+        names,
+        input_dirs: HashMap::new(),
+    };
+    watcher.watch_all();
+
+    Ok(watcher)
+}
+
+/// The paths that are always watched: the crate directories, the workspace
+/// root (for its `Cargo.toml` and `Cargo.lock`, even in a virtual workspace)
+/// and the additionally configured paths.
+fn base_paths(
+    root: &Utf8Path,
+    dirs: &[&KrateDir],
+    extra: &[PathBuf],
+) -> Box<[(PathBuf, RecursiveMode)]> {
+    let mut paths: Vec<_> = dirs
+        .iter()
+        .map(|d| (d.path().as_std_path().to_owned(), d.rec_mode()))
+        .collect();
+
+    if !dirs.iter().any(|d| d.path() == root) {
+        paths.push((root.as_std_path().to_owned(), RecursiveMode::NonRecursive));
+    }
+
+    paths.extend(extra.iter().map(|p| (p.clone(), RecursiveMode::Recursive)));
+    paths.into()
+}
+
+fn krate_names(graph: &'static Graph) -> HashMap<&'static str, KrateIdx> {
+    graph
+        .collect_krates_to_watch()
+        .flat_map(|k| k.names().iter().map(|n| (n.as_str(), k.idx())))
+        .collect()
+}
+
+impl KrateDir {
+    fn rec_mode(&self) -> RecursiveMode {
+        match self {
+            KrateDir::Src(_) => RecursiveMode::Recursive,
+            KrateDir::Root(_) => RecursiveMode::NonRecursive,
+        }
+    }
+}
+
+/// Collect the patterns from the `.gitignore` files in the workspace root and
+/// crate roots, and the configured ignore patterns (which take precedence).
+fn collect_ignores(root: &Utf8Path, dirs: &[&KrateDir], configured: &[String]) -> Box<[Ignore]> {
+    let mut bases: Vec<_> = dirs
+        .iter()
+        .filter(|d| matches!(d, KrateDir::Root(_)))
+        .map(|d| d.path())
+        .chain([root])
+        .collect();
+    bases.sort_unstable();
+    bases.dedup();
+
+    let mut ignore = vec![];
+    for base in bases {
+        for pattern in read_gitignore(base) {
+            ignore.push(Ignore::new(base, &pattern));
+        }
+    }
+
+    for pattern in configured {
+        ignore.push(Ignore::new(root, pattern));
+    }
+
+    ignore.into()
+}
+
+/// Read the patterns of `dir/.gitignore`, if it exists.
+fn read_gitignore(dir: &Utf8Path) -> Vec<String> {
+    let path = dir.join(".gitignore");
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            log::warn!("Failed to read {path:?}: {e}");
+            return vec![];
+        }
+    };
+
+    let patterns = parse_gitignore(&content);
+    log::trace!("Read {} patterns from {path:?}", patterns.len());
+    patterns
+}
+
+/// Parse the patterns of a `.gitignore`, skipping comments and blank lines.
+fn parse_gitignore(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// An ignore pattern, relative to `base`.
+struct Ignore {
+    base: Utf8PathBuf,
+    glob: Glob,
+    /// Whether the pattern starts with `!`, i.e., re-includes paths that
+    /// earlier patterns ignore.
+    negated: bool,
+}
+
+impl Ignore {
+    fn new(base: &Utf8Path, pattern: &str) -> Self {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(p) => (true, p),
+            None => (false, pattern),
+        };
+        Self {
+            base: base.to_owned(),
+            glob: Glob::new(pattern),
+            negated,
+        }
+    }
+}
+
+/// The state of the handler that is derived from the crate graph.
+struct Scope {
+    /// The watched crate directories, to attribute changes to crates.
+    krate_dirs: Box<[(PathBuf, RecursiveMode, KrateIdx)]>,
+    /// Ignore patterns, in order of increasing precedence.
+    ignore: Box<[Ignore]>,
+}
+
+impl Scope {
+    fn new(graph: &Graph, dirs: &[&KrateDir]) -> Self {
+        let krate_dirs = graph
+            .collect_krates_to_watch()
+            .flat_map(|k| {
+                k.dirs()
+                    .iter()
+                    .map(|d| (d.path().as_std_path().to_owned(), d.rec_mode(), k.idx()))
+            })
+            .collect();
+
+        let ignore = collect_ignores(graph.workspace_root(), dirs, &graph.config().ignore);
+
+        Scope { krate_dirs, ignore }
+    }
+}
+
+#[derive(Clone)]
+struct EvHandler {
+    latest: LatestPublisher<Instant>,
+    /// Set if the backend reported that events were lost.
+    overflowed: Arc<AtomicBool>,
+    /// Inputs of the build, which are always relevant.
+    inputs: Arc<Mutex<Inputs>>,
+    dirty: Arc<Mutex<Dirty>>,
+    /// Shared with the watcher, which replaces it when the graph is reloaded.
+    scope: Arc<Mutex<Scope>>,
+    reported_event_err: bool,
+    /// The root that asset patterns are relative to.
+    root: Utf8PathBuf,
+    assets: Box<[Glob]>,
+    /// Additionally watched paths, in which every change is relevant.
+    extra: Box<[PathBuf]>,
+}
+
+impl EventHandler for EvHandler {
+    fn handle_event(&mut self, event: notify::Result<notify::Event>) {
+        let event = match event {
+            Ok(ev) => ev,
+            Err(err) => return self.report_event_err(&err),
+        };
+
+        if event.need_rescan() {
+            if !self.overflowed.swap(true, Relaxed) {
+                log::warn!("The file watcher lost events, rebuilding to catch up");
+            }
+            self.latest.publish(Instant::now());
+            return;
+        }
+
+        match event.kind {
+            EventKind::Any | EventKind::Other | EventKind::Access(_) => return,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => (),
+        }
+
+        let mut dirty = Dirty::default();
+        for path in &event.paths {
+            self.attribute(path, &mut dirty);
+        }
+
+        if !dirty.is_empty() {
+            self.dirty.lock().extend(dirty);
+            self.latest.publish(Instant::now());
+        }
+    }
+}
+
+impl EvHandler {
+    /// Record the crates affected by a change to `path` in `dirty`, if the
+    /// change could affect compilation.
+    fn attribute(&self, path: &Path, dirty: &mut Dirty) {
+        let inputs = self.inputs.lock();
+
+        let krates = inputs.krates_of(path);
+        if !krates.is_empty() {
+            for krate in krates {
+                dirty.add(krate);
+            }
+            if is_manifest(path) {
+                dirty.add_manifest();
+            }
+            return;
+        }
+
+        let scope = self.scope.lock();
+        let owner = scope
+            .krate_dirs
+            .iter()
+            .filter(|(dir, mode, _)| match mode {
+                RecursiveMode::Recursive => path.starts_with(dir),
+                RecursiveMode::NonRecursive => path.parent() == Some(dir),
+            })
+            .max_by_key(|(dir, _, _)| dir.as_os_str().len());
+
+        let relevant = match Utf8Path::from_path(path) {
+            // Non-UTF-8 paths can't be Rust sources or manifests.
+            None => self.is_extra(path),
+            // Lockfiles in particular are commonly ignored.
+            Some(path) if is_manifest(path.as_std_path()) => true,
+            Some(path) if is_ignored(&scope.ignore, path) => false,
+            Some(path) if path.extension() == Some("rs") => {
+                // Sources in `src` that the compiler didn't read aren't part of
+                // the build (yet), e.g. undeclared modules or other binaries.
+                // After a failed build, they may be about to be.
+                let compiled = !inputs.is_complete()
+                    || owner.is_none_or(|(_, m, _)| *m != RecursiveMode::Recursive);
+                if !compiled {
+                    log::trace!("Ignoring change to uncompiled source: {path:?}");
+                    return;
+                }
+                true
+            }
+            Some(path) => self.is_extra(path.as_std_path()) || self.is_asset(path),
+        };
+
+        if !relevant {
+            log::trace!("Ignoring change: {path:?}");
+            return;
+        }
+
+        if is_manifest(path) {
+            dirty.add_manifest();
+        }
+
+        match owner {
+            Some(&(_, _, krate)) => dirty.add(krate),
+            None => dirty.add_other(),
+        }
+    }
+
+    fn is_asset(&self, path: &Utf8Path) -> bool {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        self.assets.iter().any(|g| g.matches(rel.as_str()))
+    }
+
+    fn is_extra(&self, path: &Path) -> bool {
+        self.extra.iter().any(|e| path.starts_with(e))
+    }
+
+    fn report_event_err(&mut self, err: &notify::Error) {
+        if self.reported_event_err {
+            log::trace!("Watcher error: {err}");
+        } else {
+            self.reported_event_err = true;
+            log::warn!("Watcher error: {err}");
+            log::warn!("Future Watcher errors will be logged only as TRACE");
+        }
+    }
+}
+
+/// Like with Git, the last pattern that matches `path` decides.
+fn is_ignored(ignore: &[Ignore], path: &Utf8Path) -> bool {
+    ignore
+        .iter()
+        .rev()
+        .find(|i| {
+            path.strip_prefix(&i.base)
+                .is_ok_and(|rel| i.glob.matches(rel.as_str()))
+        })
+        .is_some_and(|i| !i.negated)
+}
+
+fn is_manifest(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|n| n == "Cargo.toml" || n == "Cargo.lock")
+}
+
+fn extract_dirs(graph: &Graph) -> Result<Box<[&KrateDir]>> {
+    let mut dirs = HashMap::new();
+
+    for krate in graph.collect_krates_to_watch() {
+        for dir in krate.dirs() {
+            let path = dir.path();
+
+            let did_insert = dirs.try_insert(path, dir).is_ok();
+
+            ensure!(did_insert, "Duplicate crate path: {path:?}");
+        }
+    }
+
+    let mut dirs: Box<[_]> = dirs.into_values().collect();
+    dirs.sort_unstable_by_key(|dir| dir.path());
+
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::latest::make_latest;
+    use pretty_assertions::assert_eq;
+
+    const ROOT: &str = "/ws";
+
+    fn handler() -> EvHandler {
+        let (latest, _) = make_latest(Instant::now());
+        EvHandler {
+            latest,
+            overflowed: Arc::default(),
+            inputs: Arc::default(),
+            dirty: Arc::default(),
+            scope: Arc::new(Mutex::new(Scope {
+                krate_dirs: [].into(),
+                ignore: [].into(),
+            })),
+            reported_event_err: false,
+            root: ROOT.into(),
+            assets: [].into(),
+            extra: [].into(),
+        }
+    }
+
+    #[test]
+    fn watches_virtual_workspace_root() {
+        let member = KrateDir::Root(format!("{ROOT}/member").into());
+        let paths = base_paths(ROOT.into(), &[&member], &[]);
+
+        assert_eq!(
+            paths.iter().filter(|(p, _)| p == Path::new(ROOT)).count(),
+            1
+        );
+        assert!(paths.contains(&(ROOT.into(), RecursiveMode::NonRecursive)));
+    }
+
+    #[test]
+    fn root_manifest_reloads_graph() {
+        for name in ["Cargo.toml", "Cargo.lock"] {
+            let mut dirty = Dirty::default();
+            handler().attribute(Path::new(&format!("{ROOT}/{name}")), &mut dirty);
+
+            assert!(dirty.manifest_changed(), "{name}");
+            assert!(!dirty.is_empty(), "{name}");
+        }
+
+        let mut dirty = Dirty::default();
+        handler().attribute(Path::new(&format!("{ROOT}/README.md")), &mut dirty);
+        assert!(dirty.is_empty());
+    }
+
+    #[test]
+    fn undeclared_source() {
+        let src = format!("{ROOT}/src");
+        let krate = KrateIdx::new_test(0);
+        let handler = handler();
+        handler.scope.lock().krate_dirs =
+            [(src.clone().into(), RecursiveMode::Recursive, krate)].into();
+        handler
+            .inputs
+            .lock()
+            .add_file(format!("{src}/main.rs").into(), krate);
+
+        let foo = PathBuf::from(format!("{src}/foo.rs"));
+        let mut dirty = Dirty::default();
+        handler.attribute(&foo, &mut dirty);
+        assert!(dirty.is_empty());
+
+        // E.g. `mod foo;` was added before `foo.rs` was created.
+        handler.inputs.lock().set_outdated();
+        handler.attribute(&foo, &mut dirty);
+        assert!(!dirty.is_empty());
+    }
+
+    #[test]
+    fn gitignore() {
+        let content = "\
+# comment
+
+/target
+*.tmp   
+!keep.tmp
+\\#literal
+docs/
+";
+        assert_eq!(
+            parse_gitignore(content),
+            ["/target", "*.tmp", "!keep.tmp", "\\#literal", "docs/"]
+        );
+    }
+
+    #[test]
+    fn negated_ignore() {
+        let root = Utf8Path::new(ROOT);
+        let ignore = ["*.tmp", "!keep.tmp", "gen/", "!gen/*.rs", "\\!not-negated"]
+            .map(|p| Ignore::new(root, p));
+
+        for (rel, ignored) in [
+            ("a.tmp", true),
+            ("keep.tmp", false),
+            ("src/keep.tmp", false),
+            ("gen/out.txt", true),
+            ("gen/out.rs", false),
+            ("!not-negated", true),
+            ("not-negated", false),
+        ] {
+            let path = root.join(rel);
+            assert_eq!(is_ignored(&ignore, &path), ignored, "{rel}");
+        }
+    }
+
+    #[test]
+    fn ignored_lockfile() {
+        let handler = handler();
+        handler.scope.lock().ignore = [Ignore::new(ROOT.into(), "Cargo.lock")].into();
+
+        let mut dirty = Dirty::default();
+        handler.attribute(Path::new(&format!("{ROOT}/Cargo.lock")), &mut dirty);
+        assert!(dirty.manifest_changed());
+    }
+}
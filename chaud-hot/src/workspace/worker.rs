@@ -1,35 +1,42 @@
+use super::clean;
+use super::command::{self, Command};
 use super::graph::Graph;
-use super::watcher::Watcher;
+use super::watcher::{Dirty, Watcher};
 use crate::cargo::Builder;
 use crate::cargo::metadata::ManifestPath;
 use crate::util::minilog;
-use crate::{cycle, dylib};
+use crate::{Config, cycle, dylib, func};
 use anyhow::{Context as _, Result};
 use core::time::Duration;
+use core::{fmt, mem};
 use parking_lot::Once;
 use std::thread;
 use std::time::Instant;
 
-const DEBOUNCE: Duration = Duration::from_millis(350);
+/// How often to check for commands while waiting for the watcher.
+const COMMAND_POLL: Duration = Duration::from_millis(100);
 
 /// Launch the worker thread.
 ///
 /// This function is idempotent.
 ///
-/// If [`log`] has not been initialized yet, a minimal logger will be installed.
-pub fn launch(root_pkg_manifest: &str, feature_flags: Option<&'static str>) {
+/// If [`log`] has not been initialized yet, a minimal logger will be installed
+/// (unless disabled in the `config`).
+pub fn launch(root_pkg_manifest: &str, feature_flags: Option<&'static str>, config: Config) {
     static INIT: Once = Once::new();
 
     let root_mani = ManifestPath::new(root_pkg_manifest);
 
     INIT.call_once(move || {
-        minilog::init();
+        if config.minilog() {
+            minilog::init();
+        }
 
         log::trace!("Launching worker thread");
 
         let spawn_result = thread::Builder::new()
             .name("chaud-worker".to_owned())
-            .spawn(move || work(root_mani, feature_flags));
+            .spawn(move || work(root_mani, feature_flags, config));
 
         if let Err(e) = spawn_result {
             log::error!("Failed to spawn Chaud worker: {e:#}");
@@ -37,10 +44,10 @@ pub fn launch(root_pkg_manifest: &str, feature_flags: Option<&'static str>) {
     });
 }
 
-fn work(root_mani: ManifestPath, feature_flags: Option<&'static str>) {
+fn work(root_mani: ManifestPath, feature_flags: Option<&'static str>, config: Config) {
     log::debug!("Chaud worker thread is running");
 
-    let worker = match init(root_mani, feature_flags) {
+    let worker = match init(root_mani, feature_flags, config) {
         Ok(val) => val,
         Err(e) => {
             log::error!("Initialization failed, shutting down worker thread: {e:#}");
@@ -58,13 +65,32 @@ struct Worker {
     builder: Builder,
     watcher: Watcher,
     epoch: u32,
+    /// The crates changed since the last reload.
+    dirty: Dirty,
 }
 
-fn init(root_mani: ManifestPath, feature_flags: Option<&'static str>) -> Result<Worker> {
-    let graph = Graph::new(root_mani, feature_flags)?;
-    let builder = Builder::init(graph.env())?;
-    let watcher = Watcher::new(graph)?;
-    Ok(Worker { graph, builder, watcher, epoch: 0 })
+fn init(
+    root_mani: ManifestPath,
+    feature_flags: Option<&'static str>,
+    config: Config,
+) -> Result<Worker> {
+    let graph = Graph::new(root_mani, feature_flags, config)?;
+    if let Some(signal) = &graph.config().reload_signal {
+        if let Err(e) = command::install_signal(signal) {
+            log::warn!("Reloading on a signal is unavailable: {e:#}");
+        }
+    }
+    clean::stale_libs(graph.env().chaud_dir());
+    let builder = Builder::init(graph.env(), graph.config().reload_linker())?;
+    let mut watcher = Watcher::new(graph)?;
+    watcher.track_inputs(builder.dep_infos(), builder.build_scripts());
+    Ok(Worker {
+        graph,
+        builder,
+        watcher,
+        epoch: 0,
+        dirty: Dirty::default(),
+    })
 }
 
 fn main(mut w: Worker) {
@@ -77,50 +103,182 @@ fn main(mut w: Worker) {
     }
 }
 
-fn main_one(Worker { graph, builder, watcher, epoch }: &mut Worker) -> Result<()> {
-    let env = graph.env();
-
+fn main_one(Worker { graph, builder, watcher, epoch, dirty }: &mut Worker) -> Result<()> {
     log::debug!("Waiting for watcher...");
-    let mut last = watcher.wait();
+    let manual = graph.config().manual();
+    let mut pending = None;
+    let mut requested = false;
+    let (mut last, explicit) = loop {
+        for cmd in command::take() {
+            requested |= handle(cmd);
+        }
+
+        func::apply_deferred();
+        dylib::collect();
+
+        if let Some(l) = watcher.wait_for(COMMAND_POLL) {
+            if pending.is_none() && (manual || command::is_paused()) {
+                log::debug!("Files changed, holding back reload until requested");
+            }
+            pending = Some(l);
+        }
+
+        match pending {
+            Some(l) if requested => break (l, true),
+            Some(l) if !manual && !command::is_paused() => break (l, false),
+            None if requested => {
+                log::info!("Reload requested, but nothing changed");
+                requested = false;
+            }
+            _ => {}
+        }
+    };
+
+    // An explicitly requested reload should not wait for further changes.
+    let debounce_for = if explicit {
+        Duration::ZERO
+    } else {
+        graph.config().debounce()
+    };
 
     'has_dirty: loop {
-        debounce(&mut last, watcher);
+        debounce(debounce_for, &mut last, watcher);
+
+        dirty.extend(watcher.take_dirty());
+        log::debug!("Changed crates: {}", dirty.describe(graph));
+
+        if dirty.manifest_changed() {
+            refresh(graph, watcher, dirty)?;
+        }
 
         log::debug!("Preparing & building...");
 
+        let start = Instant::now();
         if let Err(e) = builder.build() {
             log::info!("{e:#}");
+            watcher.build_failed();
             // `cargo build` failing is expected, so don't return an error.
             return Ok(());
         }
 
+        let build = start.elapsed();
+        watcher.track_inputs(builder.dep_infos(), builder.build_scripts());
+
         if let Some(l) = watcher.check() {
             log::debug!("Dirty after build, starting over");
             last = l;
             continue 'has_dirty;
         }
 
+        if !builder.has_changes() {
+            let changed = mem::take(dirty).describe(graph);
+            log::info!("Nothing to reload, the build output is unchanged (changed: {changed})");
+            return Ok(());
+        }
+
         *epoch = epoch.checked_add(1).context("Epoch overflowed")?;
+        let env = graph.env();
         let dst = env
             .chaud_dir()
-            .join(format!("{}.{epoch}.hot", env.bin().as_str()));
+            .join(clean::lib_name(env.bin().as_str(), *epoch));
+        let start = Instant::now();
         builder.link_latest(&dst)?;
+        let link = start.elapsed();
 
         log::debug!("Loading {dst:?}...");
-        dylib::load(&dst)?;
+        let start = Instant::now();
+        let mut txn = func::begin();
+        dylib::load(&mut txn, *epoch, &dst)?;
+        builder.did_load();
+        let timings = Timings { build, link, load: start.elapsed() };
 
-        log::info!("Reload complete");
-        cycle::did_reload();
+        let changed = mem::take(dirty).describe(graph);
+        if cycle::is_deferred() {
+            log::info!(
+                "Reload loaded (changed: {changed}; {timings}), waiting for the application to apply it"
+            );
+        } else {
+            txn.commit();
+            log::info!("Reload complete (changed: {changed}; {timings})");
+            cycle::did_reload();
+        }
 
         return Ok(());
     }
 }
 
+/// How long the steps of a reload took.
+struct Timings {
+    build: Duration,
+    link: Duration,
+    load: Duration,
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "build {:.2}s, link {:.2}s, load {:.2}s",
+            self.build.as_secs_f32(),
+            self.link.as_secs_f32(),
+            self.load.as_secs_f32()
+        )
+    }
+}
+
+/// Reload the crate graph after a manifest changed, and switch to it if that
+/// is possible without a restart.
+fn refresh(graph: &mut &'static Graph, watcher: &mut Watcher, dirty: &mut Dirty) -> Result<()> {
+    log::debug!("Manifest changed, reloading crate graph...");
+
+    // If this fails, the manifest is probably being edited. Keep the change
+    // pending, to try again on the next file change.
+    let new = graph.reload()?;
+
+    dirty.clear_manifest();
+
+    let diff = match graph.diff(new) {
+        Ok(diff) => diff,
+        Err(e) => {
+            log::error!(
+                "A manifest change can't be applied without a restart, restart the application to pick it up: {e:#}"
+            );
+            return Ok(());
+        }
+    };
+
+    log::info!("Reloaded crate graph ({diff})");
+
+    watcher.refresh(new)?;
+    *dirty = mem::take(dirty).remap(graph, new);
+    *graph = new;
+
+    Ok(())
+}
+
+/// Returns `true` if a reload was requested.
+fn handle(cmd: Command) -> bool {
+    log::debug!("Handling command: {cmd:?}");
+
+    match cmd {
+        Command::Rollback => match func::begin().rollback() {
+            Some(count) => {
+                log::info!("Rollback complete, reverted {count} functions");
+                cycle::did_reload();
+            }
+            None => log::info!("Rollback requested, but there is nothing to roll back"),
+        },
+        Command::Reload => return true,
+    }
+
+    false
+}
+
 #[expect(clippy::needless_continue, reason = "intentionally explicit")]
-fn debounce(last: &mut Instant, watcher: &mut Watcher) {
+fn debounce(duration: Duration, last: &mut Instant, watcher: &mut Watcher) {
     log::trace!("Debouncing...");
     loop {
-        match DEBOUNCE.checked_sub(last.elapsed()) {
+        match duration.checked_sub(last.elapsed()) {
             Some(remaining) => {
                 thread::sleep(remaining);
                 // Check for any updates while we slept.
@@ -126,8 +126,11 @@ fn hot(input: &HotInput) -> TokenStream {
         storage(input),
         reload(input),
 
-        @"__chaud_FUNC.get()",
-        paren![sep(',', input.arg_idents_outer())]
+        @"__chaud_FUNC.call",
+        paren![
+            @"|__chaud_f| __chaud_f",
+            paren![sep(',', input.arg_idents_outer())]
+        ]
     ]
 }
 
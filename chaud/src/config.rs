@@ -0,0 +1,272 @@
+use core::time::Duration;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Configuration for [`init!`][crate::init!] and
+/// [`init_with_config`][crate::init_with_config].
+///
+/// Options that are not set here are read from the `[package.metadata.chaud]`
+/// table in the manifest of the package containing your `fn main`:
+///
+/// ```toml
+/// [package.metadata.chaud]
+/// debounce-ms = 500
+/// poll-ms = 1000
+/// watch = ["assets"]
+/// ignore = ["*.swp", "generated/**"]
+/// assets = ["*.wgsl"]
+/// packages = ["some-git-dep"]
+/// cargo-args = ["--locked"]
+/// cargo = "/path/to/cargo"
+/// profile = "dev"
+/// target = "x86_64-unknown-linux-gnu"
+/// trigger = "manual"
+/// reload-signal = "SIGUSR1"
+/// reload-linker = "mold"
+/// ```
+///
+/// Lists from the manifest are extended by the lists set here.
+///
+/// When hot-reloading is **disabled**, the configuration is ignored.
+///
+/// ## Examples
+///
+/// ```no_run
+/// use core::time::Duration;
+///
+/// let config = chaud::Config::new()
+///     .debounce(Duration::from_millis(500))
+///     .watch("assets")
+///     .ignore("*.swp");
+///
+/// chaud::init!(config);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct Config {
+    debounce: Option<Duration>,
+    poll: Option<Duration>,
+    watch: Vec<PathBuf>,
+    ignore: Vec<String>,
+    assets: Vec<String>,
+    packages: Vec<String>,
+    cargo_args: Vec<String>,
+    cargo: Option<OsString>,
+    profile: Option<String>,
+    target: Option<String>,
+    trigger: Option<Trigger>,
+    reload_signal: Option<String>,
+    reload_linker: Option<ReloadLinker>,
+    minilog: Option<bool>,
+}
+
+/// When to reload after files have changed.
+///
+/// See [`Config::trigger`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// Reload automatically, once no further changes have been made for the
+    /// [debounce duration][Config::debounce]. This is the default.
+    Auto,
+    /// Only record changes, and reload them when requested by
+    /// [`reload_now()`][crate::reload_now()] or the
+    /// [reload signal][Config::reload_signal]. In the manifest, this is
+    /// `trigger = "manual"`.
+    Manual,
+}
+
+/// The linker used to link hot-reloaded libraries.
+///
+/// See [`Config::reload_linker`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReloadLinker {
+    /// Use `mold` or `ld.lld` (in that order) if either is found in the `PATH`
+    /// on Linux, otherwise the original linker. If linking with `mold` or
+    /// `ld.lld` fails, it is retried with the original linker. This is the
+    /// default.
+    Auto,
+    /// Use the linker the application was linked with.
+    Original,
+    /// Use `ld.lld`.
+    Lld,
+    /// Use `mold`.
+    Mold,
+}
+
+impl Config {
+    /// Create a configuration without any options set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long to wait for further file changes before starting a build.
+    ///
+    /// The default is 350 milliseconds.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = Some(debounce);
+        self
+    }
+
+    /// Poll for file changes every `interval`, instead of relying on
+    /// notifications from the operating system (e.g. `inotify`).
+    ///
+    /// Use this if notifications don't arrive, e.g. for network file systems
+    /// or directories mounted into containers. By default, Chaud only falls
+    /// back to polling (with a warning) if the native watcher fails, e.g.
+    /// because the limit of watches has been reached.
+    pub fn poll(mut self, interval: Duration) -> Self {
+        self.poll = Some(interval);
+        self
+    }
+
+    /// Additionally watch `path` (recursively) for changes. Relative paths are
+    /// relative to the workspace root.
+    pub fn watch(mut self, path: impl Into<PathBuf>) -> Self {
+        self.watch.push(path.into());
+        self
+    }
+
+    /// Ignore changes to paths matching the glob `pattern`.
+    ///
+    /// `*` and `?` match within a single path component, `**` matches across
+    /// components. A pattern without a `/` is matched against every component
+    /// (e.g. the file name). Other patterns are matched against the path,
+    /// relative to the workspace root.
+    ///
+    /// As in a `.gitignore`, a pattern starting with `!` re-includes paths that
+    /// earlier patterns ignore, and the last matching pattern decides.
+    ///
+    /// Patterns from `.gitignore` files in the workspace root and in crate
+    /// directories are always respected, before the patterns set here.
+    /// Manifests and lockfiles are never ignored.
+    pub fn ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Start a build when a file matching the glob `pattern` changes, in
+    /// addition to `.rs` files, manifests and files read by the compiler
+    /// (e.g. via `include_str!`) or declared by build scripts (via
+    /// `rerun-if-changed`). Patterns are matched like
+    /// [`ignore`][Self::ignore].
+    ///
+    /// Changes to other files in crate directories are ignored, except for
+    /// files in [`watch`][Self::watch]ed paths, which always start a build.
+    pub fn asset(mut self, pattern: impl Into<String>) -> Self {
+        self.assets.push(pattern.into());
+        self
+    }
+
+    /// Watch and reload the package called `name`.
+    ///
+    /// By default, the workspace members and path dependencies (including
+    /// those from `[patch]` sections) that the binary depends on are watched.
+    /// Use this to include other dependencies, e.g. a local checkout of a git
+    /// dependency. Note that Cargo only rebuilds such packages if it
+    /// considers them changed.
+    pub fn package(mut self, name: impl Into<String>) -> Self {
+        self.packages.push(name.into());
+        self
+    }
+
+    /// Pass `arg` to every `cargo` build performed by Chaud.
+    pub fn cargo_arg(mut self, arg: impl Into<String>) -> Self {
+        self.cargo_args.push(arg.into());
+        self
+    }
+
+    /// The `cargo` binary to use.
+    ///
+    /// The default is the `CARGO` environment variable, or `cargo`.
+    pub fn cargo(mut self, cargo: impl Into<OsString>) -> Self {
+        self.cargo = Some(cargo.into());
+        self
+    }
+
+    /// The Cargo profile to build with.
+    ///
+    /// The default is the profile the application was built with, as
+    /// determined by its directory. Since `test` and `bench` share the
+    /// directories of `dev` and `release`, they have to be set explicitly.
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// The target triple to build for.
+    ///
+    /// The default is the target the application was built for (via
+    /// `--target`), or the host.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// When to reload after files have changed.
+    ///
+    /// The default is [`Trigger::Auto`]. Automatic reloading can also be
+    /// suspended temporarily using [`pause()`][crate::pause()].
+    pub fn trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Reload whenever the process receives the signal called `signal`,
+    /// like [`reload_now()`][crate::reload_now()].
+    ///
+    /// Supported are `SIGHUP`, `SIGUSR1` and `SIGUSR2`. The handler replaces
+    /// any handler previously installed by the application. By default, no
+    /// handler is installed.
+    pub fn reload_signal(mut self, signal: impl Into<String>) -> Self {
+        self.reload_signal = Some(signal.into());
+        self
+    }
+
+    /// The linker used to link hot-reloaded libraries. In the manifest, this
+    /// is one of `"auto"`, `"original"`, `"lld"` or `"mold"`.
+    ///
+    /// The default is [`ReloadLinker::Auto`]. If the original linker is a
+    /// compiler driver (e.g. `cc`), the chosen linker is selected via
+    /// `-fuse-ld`, which requires GCC 12.1 or later for `mold`. A linker
+    /// already selected via `-fuse-ld` is kept by `Auto`.
+    pub fn reload_linker(mut self, linker: ReloadLinker) -> Self {
+        self.reload_linker = Some(linker);
+        self
+    }
+
+    /// Whether to install a minimal logger if the [`log`](https://docs.rs/log)
+    /// crate has not been initialized yet (see [`init()`][crate::init()]).
+    ///
+    /// The default is `true`. This option cannot be set in the manifest.
+    pub fn minilog(mut self, minilog: bool) -> Self {
+        self.minilog = Some(minilog);
+        self
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[cfg(feature = "unsafe-hot-reload")]
+    pub fn __into_hot(self) -> crate::__internal::Config {
+        crate::__internal::Config {
+            debounce: self.debounce,
+            poll: self.poll,
+            watch: self.watch,
+            ignore: self.ignore,
+            assets: self.assets,
+            packages: self.packages,
+            cargo_args: self.cargo_args,
+            cargo: self.cargo,
+            profile: self.profile,
+            target: self.target,
+            manual: self.trigger.map(|t| t == Trigger::Manual),
+            reload_signal: self.reload_signal,
+            reload_linker: self.reload_linker.map(|l| match l {
+                ReloadLinker::Auto => crate::__internal::ReloadLinker::Auto,
+                ReloadLinker::Original => crate::__internal::ReloadLinker::Original,
+                ReloadLinker::Lld => crate::__internal::ReloadLinker::Lld,
+                ReloadLinker::Mold => crate::__internal::ReloadLinker::Mold,
+            }),
+            minilog: self.minilog,
+        }
+    }
+}
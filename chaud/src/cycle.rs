@@ -21,9 +21,106 @@ mod imp {
 
     #[inline]
     #[track_caller]
+    #[expect(
+        clippy::panic,
+        clippy::missing_panics_doc,
+        reason = "documented on the public wrappers"
+    )]
     pub fn wait(_: &mut ()) {
         panic!("Cannot wait for hot-reload if hot-reloading is disabled.")
     }
+
+    #[inline]
+    pub fn set_deferred(_: bool) {}
+
+    #[inline]
+    pub fn apply_pending() -> bool {
+        false
+    }
+
+    #[inline]
+    pub fn set_auto_rollback(_: u32, _: bool) {}
+
+    #[inline]
+    pub fn set_unloading(_: Option<u32>) {}
+}
+
+/// Enable or disable deferred reloads.
+///
+/// By default, the functions of a hot-reloaded library are updated as soon as
+/// the library is loaded, while other threads may be in the middle of their
+/// work.
+///
+/// When deferred reloads are enabled, loaded updates are instead queued until
+/// [`apply_pending`] is called, e.g. at the top of a game loop or between two
+/// requests. Disabling deferred reloads applies any queued updates.
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+#[inline]
+pub fn set_deferred(deferred: bool) {
+    imp::set_deferred(deferred);
+}
+
+/// Apply all updates queued since the last call, if [deferred
+/// reloads][set_deferred] are enabled.
+///
+/// Returns `true` if any updates were applied. [`Check`] and [`Track`] only
+/// observe a deferred reload once it has been applied.
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+#[inline]
+#[expect(clippy::must_use_candidate, reason = "usually called for its effect")]
+pub fn apply_pending() -> bool {
+    imp::apply_pending()
+}
+
+/// What to roll back when a function panics shortly after being reloaded.
+///
+/// See [`set_auto_rollback`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RollbackScope {
+    /// Only the function that panicked.
+    Function,
+    /// Every function updated by the latest reload, like [`crate::rollback`].
+    Reload,
+}
+
+/// Automatically roll back hot-reloaded code that panics within `calls` calls
+/// after being reloaded.
+///
+/// Only panics that unwind through a `#[chaud::hot]` function are detected. A
+/// completed rollback is logged as a warning and observed as a reload by
+/// [`Check`] and [`Track`]. Passing `0` disables automatic rollback, which is
+/// the default.
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+#[inline]
+pub fn set_auto_rollback(calls: u32, scope: RollbackScope) {
+    imp::set_auto_rollback(calls, scope == RollbackScope::Reload);
+}
+
+/// Enable or disable unloading of superseded hot-reloaded libraries.
+///
+/// By default, every hot-reloaded library stays loaded until the application
+/// exits. With unloading enabled, a library is unloaded once none of its
+/// functions are current, could be restored by the latest `rollback_depth`
+/// [rollbacks][crate::rollback], or are still being executed (as tracked by the
+/// `#[chaud::hot]` wrappers). Older rollback states are discarded. Passing
+/// `None` disables unloading, which is the default.
+///
+/// Enable unloading before the first reload. Libraries loaded while unloading
+/// was disabled are never unloaded, and neither are libraries that add new
+/// `#[chaud::hot]` functions (which keep their state in that library).
+///
+/// Chaud cannot observe any other references into a library. Only enable
+/// unloading if the application does not keep such references across
+/// reloads, e.g. `&'static str`s or trait objects created by reloaded code,
+/// or threads spawned by it.
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+#[inline]
+pub fn set_unloading(rollback_depth: Option<u32>) {
+    imp::set_unloading(rollback_depth);
 }
 
 /// Check for hot reloads since `self` was created.
@@ -5,6 +5,10 @@
 #[cfg(feature = "unsafe-hot-reload")]
 pub use chaud_hot as __internal;
 
+pub use self::config::{Config, ReloadLinker, Trigger};
+
+mod config;
+
 pub mod cycle;
 
 /// Marks a function that should be hot-reloaded.
@@ -100,20 +104,91 @@ pub use chaud_macros::persist;
 /// automatically configure a minimal logger if the [`log`](https://docs.rs/log)
 /// crate has not already been initialized at the time it is called.
 pub fn init(root_pkg_manifest: &str) {
+    init_with_config(root_pkg_manifest, Config::new());
+}
+
+/// Initializes Chaud with the given [`Config`].
+///
+/// See [`init()`] for details.
+#[cfg_attr(
+    not(feature = "unsafe-hot-reload"),
+    expect(clippy::needless_pass_by_value, reason = "unused if disabled")
+)]
+pub fn init_with_config(root_pkg_manifest: &str, config: Config) {
     // Silence unused variable lint if hot reloading is disabled.
-    let _ = root_pkg_manifest;
+    let _ = (root_pkg_manifest, &config);
+    #[cfg(feature = "unsafe-hot-reload")]
+    __internal::init(root_pkg_manifest, None, config.__into_hot());
+}
+
+/// Reverts every hot-reloaded function to the version that was active before
+/// the most recent reload.
+///
+/// Calling this repeatedly goes back further, up to the initially compiled
+/// versions. The next reload applies the entire latest build again, including
+/// changes that were rolled back.
+///
+/// # Behavior
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+///
+/// When hot-reloading is **enabled**, this asks the worker thread to perform
+/// the rollback and returns immediately. A completed rollback is observed as a
+/// reload by [`cycle::Check`] and [`cycle::Track`]. Rollbacks are applied
+/// immediately, even if [deferred reloads][cycle::set_deferred] are enabled,
+/// and discard any deferred updates.
+pub fn rollback() {
+    #[cfg(feature = "unsafe-hot-reload")]
+    __internal::rollback();
+}
+
+/// Reloads all changes made since the last reload.
+///
+/// This is primarily useful with [`Trigger::Manual`], or while reloading is
+/// [paused][pause()].
+///
+/// # Behavior
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+///
+/// When hot-reloading is **enabled**, this asks the worker thread to build and
+/// load any changed files (without waiting for further changes) and returns
+/// immediately. A completed reload is observed by [`cycle::Check`] and
+/// [`cycle::Track`]. If nothing changed, the request is ignored.
+pub fn reload_now() {
+    #[cfg(feature = "unsafe-hot-reload")]
+    __internal::reload_now();
+}
+
+/// Suspends automatic reloading until [`resume()`] is called.
+///
+/// File changes are still recorded while paused, and are reloaded after
+/// resuming. Explicit requests via [`reload_now()`] (or the
+/// [reload signal][Config::reload_signal]) are still honored.
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+pub fn pause() {
     #[cfg(feature = "unsafe-hot-reload")]
-    __internal::init(root_pkg_manifest, None);
+    __internal::pause();
+}
+
+/// Resumes automatic reloading after [`pause()`].
+///
+/// When hot-reloading is **disabled**, this is a no-op.
+pub fn resume() {
+    #[cfg(feature = "unsafe-hot-reload")]
+    __internal::resume();
 }
 
 #[doc(hidden)]
 #[macro_export]
 #[cfg(feature = "unsafe-hot-reload")]
 macro_rules! __init {
-    () => {
+    ($config:expr) => {
         $crate::__internal::init(
             env!("CARGO_MANIFEST_PATH"),
             option_env!("__CHAUD_RUSTC_FEATURE_FLAGS"),
+            $crate::Config::__into_hot($config),
         )
     };
 }
@@ -122,9 +197,9 @@ macro_rules! __init {
 #[macro_export]
 #[cfg(not(feature = "unsafe-hot-reload"))]
 macro_rules! __init {
-    () => {
-        ()
-    };
+    ($config:expr) => {{
+        let _: $crate::Config = $config;
+    }};
 }
 
 /// Initializes Chaud.
@@ -140,10 +215,16 @@ macro_rules! __init {
 /// * The `CHAUD_FEATURE_FLAGS` environment variable is consumed at build time,
 ///   which is required to make feature detection with `chaud-rustc` work.
 ///
+/// Optionally, a [`Config`] can be passed as the argument, e.g.
+/// `chaud::init!(chaud::Config::new().ignore("*.swp"))`.
+///
 /// See the [`init()`] documentation for further details.
 #[macro_export]
 macro_rules! init {
     () => {
-        $crate::__init!()
+        $crate::__init!($crate::Config::new())
+    };
+    ($config:expr $(,)?) => {
+        $crate::__init!($config)
     };
 }
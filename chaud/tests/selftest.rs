@@ -31,6 +31,13 @@ fn main() {
     eprintln!("\n---\nSELFTEST: `cargo chaud --release`\n---");
     selftest(&dirs, &["chaud", "-Fselftest", "--release"], &[]);
 
+    eprintln!("\n---\nSELFTEST: `cargo chaud` (unloading)\n---");
+    selftest(
+        &dirs,
+        &["chaud", "-Fselftest"],
+        &[("CHAUD_SELFTEST_UNLOADING", "1")],
+    );
+
     eprintln!("\n---\nSELFTEST: `RUSTC_WRAPPER=\"...\" cargo run`\n---");
     selftest(
         &dirs,
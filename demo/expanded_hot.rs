@@ -23,7 +23,7 @@ fn unit() {
     #[unsafe(export_name = "_CHAUD::expand::unit")]
     static __chaud_FUNC: ::chaud::__internal::FuncStorage<__chaud_func> =
         ::chaud::__internal::FuncStorage::new();
-    __chaud_FUNC.get()()
+    __chaud_FUNC.call(|__chaud_f| __chaud_f())
 }
 
 #[doc = " Hello, world."]
@@ -39,7 +39,7 @@ pub(crate) fn single_with_attrs<'a>(p0: &'a u32) -> u32 {
     #[unsafe(export_name = "_CHAUD::expand::single_with_attrs")]
     static __chaud_FUNC: ::chaud::__internal::FuncStorage<__chaud_func> =
         ::chaud::__internal::FuncStorage::new();
-    __chaud_FUNC.get()(p0)
+    __chaud_FUNC.call(|__chaud_f| __chaud_f(p0))
 }
 
 #[inline]
@@ -55,7 +55,7 @@ pub fn multi<'a, 'b>(p0: &'b bool, p1: &'a u32, p2: &'a u32) -> (&'a u32, &'b bo
     #[unsafe(export_name = "_CHAUD::expand::multi")]
     static __chaud_FUNC: ::chaud::__internal::FuncStorage<__chaud_func> =
         ::chaud::__internal::FuncStorage::new();
-    __chaud_FUNC.get()(p0, p1, p2)
+    __chaud_FUNC.call(|__chaud_f| __chaud_f(p0, p1, p2))
 }
 
 pub struct Collector {
@@ -81,6 +81,6 @@ impl Collector {
         #[unsafe(export_name = "_CHAUD::expand::collect")]
         static __chaud_FUNC: ::chaud::__internal::FuncStorage<__chaud_func> =
             ::chaud::__internal::FuncStorage::new();
-        __chaud_FUNC.get()(self, p1)
+        __chaud_FUNC.call(|__chaud_f| __chaud_f(self, p1))
     }
 }
@@ -51,7 +51,7 @@ fn unit() {
             __chaud_FUNC.update();
         }
     }
-    __chaud_FUNC.get()()
+    __chaud_FUNC.call(|__chaud_f| __chaud_f())
 }
 #[doc = " Hello, world."]
 #[cold]
@@ -90,7 +90,7 @@ pub(crate) fn single_with_attrs<'a>(p0: &'a u32) -> u32 {
             __chaud_FUNC.update();
         }
     }
-    __chaud_FUNC.get()(p0)
+    __chaud_FUNC.call(|__chaud_f| __chaud_f(p0))
 }
 #[inline]
 pub fn multi<'a, 'b>(p0: &'b bool, p1: &'a u32, p2: &'a u32) -> (&'a u32, &'b bool) {
@@ -129,7 +129,7 @@ pub fn multi<'a, 'b>(p0: &'b bool, p1: &'a u32, p2: &'a u32) -> (&'a u32, &'b bo
             __chaud_FUNC.update();
         }
     }
-    __chaud_FUNC.get()(p0, p1, p2)
+    __chaud_FUNC.call(|__chaud_f| __chaud_f(p0, p1, p2))
 }
 pub struct Collector {
     buf: Mutex<Vec<String>>,
@@ -177,6 +177,6 @@ impl Collector {
                 __chaud_FUNC.update();
             }
         }
-        __chaud_FUNC.get()(self, p1)
+        __chaud_FUNC.call(|__chaud_f| __chaud_f(self, p1))
     }
 }
@@ -1,7 +1,10 @@
 use chaud::cycle::Track;
 use std::borrow::Cow;
+use std::env;
 use std::fs;
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
 mod crashlog;
 
@@ -13,6 +16,9 @@ impl Selftest {
     pub fn init_before_chaud() -> Self {
         crashlog::init();
         chaud_hot::cycle::track_init();
+        if env::var_os("CHAUD_SELFTEST_UNLOADING").is_some() {
+            chaud::cycle::set_unloading(Some(1));
+        }
 
         Self { track: Track::new() }
     }
@@ -48,6 +54,26 @@ pub fn run(mut track: Track, root_version: impl Fn() -> u32) {
     track.wait();
     assert_eq!(mid::version(), 42);
     assert_eq!(mid::counters(), (0, 4, 4));
+
+    chaud::rollback();
+    track.wait();
+    assert_eq!(mid::version(), 2001);
+    assert_eq!(mid::leaf_version(), 3002);
+    assert_eq!(mid::counters(), (1, 5, 5));
+
+    chaud::pause();
+    patch("root/src/main.rs", "VERSION", "1003");
+    thread::sleep(Duration::from_secs(2));
+    assert!(!track.did_reload());
+    assert_eq!(root_version(), 1002);
+
+    chaud::reload_now();
+    track.wait();
+    assert_eq!(root_version(), 1003);
+    // The entire latest build is applied again after a rollback.
+    assert_eq!(mid::version(), 42);
+    assert_eq!(mid::leaf_version(), 3002);
+    chaud::resume();
 }
 
 #[track_caller]